@@ -0,0 +1,20 @@
+#[macro_use]
+extern crate criterion;
+extern crate xhtmlchardet;
+
+use criterion::Criterion;
+use std::io::Cursor;
+
+const TEXT: &[u8] = b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"UTF-8\"?><channel><title>Example</title></channel>";
+
+fn bench_detect(c: &mut Criterion) {
+    c.bench_function("detect", |b| {
+        b.iter(|| xhtmlchardet::detect(&mut Cursor::new(TEXT), None))
+    });
+    c.bench_function("detect_bom_only", |b| {
+        b.iter(|| xhtmlchardet::detect_bom_only(&mut Cursor::new(TEXT)))
+    });
+}
+
+criterion_group!(benches, bench_detect);
+criterion_main!(benches);
@@ -0,0 +1,194 @@
+//! Memoizing detection results for text that is seen repeatedly.
+
+use std::io::{self, Read};
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, MutexGuard};
+
+use lru::LruCache;
+
+use {detect_slice, read_prefix, PREFIX_LEN};
+
+/// The prefix examined and the hint given for it.
+type CacheKey = (Vec<u8>, Option<String>);
+
+/// A detector that caches results keyed by the text examined.
+///
+/// Only the window examined by [`detect_slice`](fn.detect_slice.html) influences the result, so
+/// it's that prefix, along with the hint, that is used as the key. Text with the same prefix and
+/// hint is given the cached result without being scanned again. The prefix itself is compared,
+/// rather than a hash of it, so text crafted to collide with another can't be given its result. The least
+/// recently used results are evicted once `capacity` results are cached.
+///
+/// `CachingDetector` can be shared between threads.
+///
+/// ### Example
+///
+/// ```
+/// use std::num::NonZeroUsize;
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::CachingDetector;
+///
+/// let detector = CachingDetector::new(NonZeroUsize::new(100).unwrap());
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// assert_eq!(detector.detect_slice(text, None), vec!["iso-8859-1".to_string()]);
+/// // This one comes from the cache
+/// assert_eq!(detector.detect_slice(text, None), vec!["iso-8859-1".to_string()]);
+/// ```
+pub struct CachingDetector<F = fn(&[u8], Option<String>) -> Vec<String>> {
+    detector: F,
+    cache: Mutex<LruCache<CacheKey, Vec<String>>>,
+}
+
+impl CachingDetector {
+    /// Create a detector backed by [`detect_slice`](fn.detect_slice.html) that caches up to
+    /// `capacity` results.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        CachingDetector::with_detector(capacity, detect_slice)
+    }
+}
+
+impl<F> CachingDetector<F>
+where
+    F: Fn(&[u8], Option<String>) -> Vec<String>,
+{
+    /// Create a caching detector that uses `detector` to detect the charset of uncached text.
+    ///
+    /// `detector` is passed the window of the text examined with the default options, as per
+    /// [`Options::window_len`](struct.Options.html#method.window_len), along with the hint.
+    pub fn with_detector(capacity: NonZeroUsize, detector: F) -> Self {
+        CachingDetector {
+            detector,
+            cache: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Attempt to detect the character set of the supplied byte stream.
+    ///
+    /// Unlike [`detect`](fn.detect.html), short or empty streams are not an error.
+    pub fn detect<R: Read>(
+        &self,
+        reader: &mut R,
+        hint: Option<String>,
+    ) -> Result<Vec<String>, io::Error> {
+        let mut buf = [0u8; PREFIX_LEN];
+        let len = read_prefix(reader, &mut buf)?;
+
+        Ok(self.detect_slice(&buf[..len], hint))
+    }
+
+    /// Attempt to detect the character set of the supplied bytes.
+    pub fn detect_slice(&self, bytes: &[u8], hint: Option<String>) -> Vec<String> {
+        let prefix = &bytes[..bytes.len().min(PREFIX_LEN)];
+
+        let key = (prefix.to_vec(), hint.clone());
+        if let Some(charsets) = self.lock().get(&key) {
+            return charsets.clone();
+        }
+
+        // The lock isn't held while detecting so that other threads aren't held up
+        let charsets = (self.detector)(prefix, hint);
+        self.lock().put(key, charsets.clone());
+        charsets
+    }
+
+    /// The number of results currently cached.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if there are no cached results.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Remove all cached results.
+    pub fn clear(&self) {
+        self.lock().clear()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LruCache<CacheKey, Vec<String>>> {
+        // The cache is never left in an inconsistent state, so a poisoned lock is still usable
+        self.cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::io::Cursor;
+
+    const XML: &[u8] =
+        b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+
+    fn capacity(capacity: usize) -> NonZeroUsize {
+        NonZeroUsize::new(capacity).unwrap()
+    }
+
+    #[test]
+    fn test_cache_hit_skips_detection() {
+        let calls = Cell::new(0);
+        let detector = CachingDetector::with_detector(capacity(10), |bytes, hint| {
+            calls.set(calls.get() + 1);
+            detect_slice(bytes, hint)
+        });
+
+        let first = detector.detect(&mut Cursor::new(XML), None).unwrap();
+        let second = detector.detect(&mut Cursor::new(XML), None).unwrap();
+        assert_eq!(first, vec!["iso-8859-1".to_string()]);
+        assert_eq!(second, first);
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_cache_keyed_by_hint() {
+        let calls = Cell::new(0);
+        let detector = CachingDetector::with_detector(capacity(10), |bytes, hint| {
+            calls.set(calls.get() + 1);
+            detect_slice(bytes, hint)
+        });
+
+        detector.detect_slice(b"<p>Hello</p>", None);
+        let hinted = detector.detect_slice(b"<p>Hello</p>", Some("windows-1252".to_string()));
+        assert_eq!(hinted, vec!["windows-1252".to_string()]);
+        assert_eq!(calls.get(), 2);
+        assert_eq!(detector.len(), 2);
+    }
+
+    #[test]
+    fn test_cache_keyed_by_text() {
+        let detector = CachingDetector::new(capacity(10));
+        let latin1 = detector.detect_slice(XML, None);
+        let utf8 = detector.detect_slice(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>", None);
+        assert_eq!(latin1, vec!["iso-8859-1".to_string()]);
+        assert_eq!(utf8, vec!["utf-8".to_string()]);
+        assert_eq!(detector.len(), 2);
+    }
+
+    #[test]
+    fn test_cache_ignores_text_beyond_window() {
+        let detector = CachingDetector::new(capacity(10));
+        let mut text = XML.to_vec();
+        text.resize(PREFIX_LEN, b' ');
+        let mut longer = text.clone();
+        longer.extend_from_slice(b"<p>more</p>");
+
+        detector.detect_slice(&text, None);
+        detector.detect_slice(&longer, None);
+        assert_eq!(detector.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let detector = CachingDetector::new(capacity(1));
+        detector.detect_slice(XML, None);
+        detector.detect_slice(b"<p>Hello</p>", None);
+        assert_eq!(detector.len(), 1);
+
+        detector.clear();
+        assert!(detector.is_empty());
+    }
+}
@@ -0,0 +1,878 @@
+//! Composable detection pipelines.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+use {
+    ascii_bytes, bare_unicode_width, bom_charset, conflicts_with_bom, detect_descriptor, endianify,
+    guess_multibyte_charset, has_mojibake, has_obfuscated_declaration, is_ebcdic, is_nul_padding,
+    language_charsets, mask_xml_processing_instructions, multibyte_utf8_outweighs, normalise,
+    parse_hint, search_ass_charset, search_charset_processing_instruction, search_css_charset,
+    search_declaration, search_language, search_past_leading_junk, search_po_header,
+    search_rtf_code_page, search_vcard_charsets, utf32_label, utf8_prefix_len, ContentKind,
+    Declared, Descriptor, Detection, DetectionResult, DetectionSource, DetectionWarning, Options,
+    UTF_8,
+};
+#[cfg(feature = "std")]
+use {read_prefix, PREFIX_LEN};
+
+/// What a detection `Stage` has to work with.
+pub struct DetectContext<'a> {
+    bytes: &'a [u8],
+    // The text following any leading junk skipped in lenient mode
+    text: &'a [u8],
+    ascii_bytes: &'a [u8],
+    hints: &'a [String],
+    descriptor: Option<&'a Descriptor>,
+    declared: Option<&'a Declared>,
+    options: &'a Options,
+    detections: &'a [Detection],
+}
+
+impl<'a> DetectContext<'a> {
+    /// The text being detected, at most the first
+    /// [`Options::window_len`](struct.Options.html#method.window_len) bytes of it.
+    pub fn bytes(&self) -> &[u8] {
+        self.bytes
+    }
+
+    /// The bytes of the text that hold the ASCII range, as determined from the first four bytes.
+    ///
+    /// UTF-16 and UCS-4 text is decoded to UTF-8, allowing ASCII declarations to be searched for
+    /// regardless of the width of the encoding.
+    /// Processing instructions such as `<?xml-stylesheet ... ?>` are blanked out with spaces.
+    pub fn ascii_bytes(&self) -> &[u8] {
+        self.ascii_bytes
+    }
+
+    /// The most preferred charset in the hint supplied by the caller, normalised.
+    pub fn hint(&self) -> Option<&str> {
+        self.hints.first().map(String::as_str)
+    }
+
+    /// All of the charsets in the hint supplied by the caller, normalised and most preferred
+    /// first.
+    ///
+    /// There is more than one when the hint is a list, as per [`detect`](fn.detect.html).
+    pub fn hints(&self) -> &[String] {
+        self.hints
+    }
+
+    /// The candidates found by earlier stages, most likely first.
+    pub fn detections(&self) -> &[Detection] {
+        self.detections
+    }
+
+    fn with_detections<'b>(&self, detections: &'b [Detection]) -> DetectContext<'b>
+    where
+        'a: 'b,
+    {
+        DetectContext {
+            detections,
+            ..*self
+        }
+    }
+
+    // Text in an EBCDIC code page has nothing in common with ASCII, so searching it for ASCII
+    // declarations or testing it for UTF-8 can only turn up false positives
+    fn ebcdic(&self) -> bool {
+        self.hint().is_some_and(is_ebcdic)
+    }
+
+    fn markup(&self) -> bool {
+        !self.ebcdic() && is_markup(self.options.content_kind)
+    }
+
+    // A full window may have cut the text off part way through a character
+    fn cut_short(&self) -> bool {
+        self.bytes.len() >= self.options.window_len()
+    }
+
+    // The charsets to add for a declared or hinted charset, resolving the byte order of a bare
+    // utf-16 unless the options say otherwise
+    fn resolve(&self, charset: String) -> Vec<String> {
+        // C1 control codes are printable characters in Windows-1252, which is what was really
+        // meant
+        let mislabelled_latin1 = self.options.latin1_as_windows1252
+            && charset == "iso-8859-1"
+            && self.text.iter().any(|byte| (0x80..=0x9F).contains(byte));
+        let charset = if mislabelled_latin1 {
+            "windows-1252".to_string()
+        } else {
+            charset
+        };
+        if self.options.emit_bare_unicode_widths {
+            vec![charset]
+        } else {
+            endianify(&charset, self.descriptor, self.text)
+        }
+    }
+}
+
+fn is_markup(content_kind: ContentKind) -> bool {
+    match content_kind {
+        ContentKind::Markup | ContentKind::Html => true,
+        ContentKind::OfficeXml
+        | ContentKind::Po
+        | ContentKind::VCard
+        | ContentKind::Ass
+        | ContentKind::DelimitedText
+        | ContentKind::Rtf
+        | ContentKind::Css => false,
+    }
+}
+
+/// A step in a `DetectorChain`.
+///
+/// Stages are run in order, each returning the charset it finds, if any. The chain normalises
+/// the charset and resolves the byte order of a bare `utf-16` before adding it to the candidates.
+/// Charsets already found by an earlier stage are skipped.
+///
+/// Closures taking a `&DetectContext` can be used as stages.
+pub trait Stage {
+    /// Attempt to determine the charset of the text in `ctx`.
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection>;
+
+    /// Attempt to determine the charsets of the text in `ctx`, most likely first.
+    ///
+    /// The default returns the result of `detect`, for stages that can find more than one charset
+    /// to override.
+    fn detect_all(&self, ctx: &DetectContext) -> Vec<Detection> {
+        self.detect(ctx).into_iter().collect()
+    }
+
+    /// Add the charsets of the text in `ctx` to `result`, which holds the candidates found by
+    /// earlier stages.
+    ///
+    /// This is what the chain calls. The default adds the charsets returned by `detect_all` as
+    /// described above. Stages that report warnings or rearrange earlier candidates override it,
+    /// finding the candidates in `result`, as `ctx.detections()` is empty here.
+    fn apply(&self, ctx: &DetectContext, result: &mut DetectionResult) {
+        let detections = self.detect_all(&ctx.with_detections(&result.detections));
+        for Detection { charset, source } in detections {
+            for charset in ctx.resolve(normalise(charset)) {
+                result.push(charset, source);
+            }
+        }
+    }
+}
+
+impl<F> Stage for F
+where
+    F: Fn(&DetectContext) -> Option<Detection>,
+{
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        self(ctx)
+    }
+}
+
+/// The charset indicated by a byte order mark or the arrangement of the first four bytes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ByteOrderMarkStage;
+
+impl ByteOrderMarkStage {
+    fn charset(ctx: &DetectContext) -> Option<&'static str> {
+        let charset = bom_charset(ctx.descriptor)?;
+        Some(if ctx.options.emit_bare_unicode_widths {
+            bare_unicode_width(charset)
+        } else if ctx.options.prefer_utf32_labels {
+            utf32_label(charset)
+        } else {
+            charset
+        })
+    }
+}
+
+impl Stage for ByteOrderMarkStage {
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        ByteOrderMarkStage::charset(ctx).map(|charset| Detection {
+            charset: charset.to_string(),
+            source: DetectionSource::ByteOrderMark,
+        })
+    }
+
+    fn apply(&self, ctx: &DetectContext, result: &mut DetectionResult) {
+        if let Some(detection) = self.detect(ctx) {
+            if ctx.options.record_agreeing_bom {
+                result.detections.push(detection);
+            } else {
+                result.push(detection.charset, detection.source);
+            }
+        }
+    }
+}
+
+// The declaration found by `detect` if it's the one named `name`
+fn declaration(ctx: &DetectContext, name: &str) -> Option<Detection> {
+    ctx.declared
+        .filter(|declared| declared.name == name)
+        .map(|declared| Detection {
+            charset: declared.value.clone(),
+            source: DetectionSource::Declaration,
+        })
+}
+
+// Add the declaration named `name` to `result`, unless a UTF-8 byte order mark, which is
+// authoritative as per the HTML and XML specifications, says otherwise
+fn apply_declaration(ctx: &DetectContext, name: &str, result: &mut DetectionResult) {
+    let declared = match ctx.declared {
+        Some(declared) if declared.name == name => declared,
+        _ => return,
+    };
+
+    result.declaration_offset = Some(ctx.bytes.len() - ctx.text.len() + declared.offset);
+    if declared.truncated {
+        result.warnings.push(DetectionWarning::TruncatedDeclaration);
+    }
+    let charset = normalise(&declared.value);
+    let conflicting = conflicts_with_bom(&charset, ctx.descriptor);
+    let overridden = conflicting && ctx.descriptor == Some(&UTF_8);
+    if conflicting && (ctx.options.strict || overridden) {
+        result
+            .warnings
+            .push(DetectionWarning::ConflictingByteOrderMark);
+    }
+    if !overridden {
+        for charset in ctx.resolve(charset) {
+            result.push(charset, DetectionSource::Declaration);
+        }
+    }
+}
+
+/// The charset from an `encoding=` declaration, as used by the XML declaration.
+///
+/// This is skipped when a `charset=` declaration comes first, as the first declaration wins.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct XmlDeclarationStage;
+
+impl Stage for XmlDeclarationStage {
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        declaration(ctx, "encoding")
+    }
+
+    fn apply(&self, ctx: &DetectContext, result: &mut DetectionResult) {
+        apply_declaration(ctx, "encoding", result);
+    }
+}
+
+/// The charset from a `charset=` declaration, as used by HTML meta tags.
+///
+/// This is skipped when an `encoding=` declaration comes first, as the first declaration wins, or
+/// when an earlier stage found a declaration.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MetaCharsetStage;
+
+impl Stage for MetaCharsetStage {
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        if ctx
+            .detections
+            .iter()
+            .any(|detection| detection.source == DetectionSource::Declaration)
+        {
+            return None;
+        }
+
+        declaration(ctx, "charset")
+    }
+
+    fn apply(&self, ctx: &DetectContext, result: &mut DetectionResult) {
+        if self
+            .detect(&ctx.with_detections(&result.detections))
+            .is_some()
+        {
+            apply_declaration(ctx, "charset", result);
+        }
+    }
+}
+
+// The charsets declared by the text when it isn't markup, in the way of its content kind
+struct ContentKindStage;
+
+impl Stage for ContentKindStage {
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        self.detect_all(ctx).into_iter().next()
+    }
+
+    fn detect_all(&self, ctx: &DetectContext) -> Vec<Detection> {
+        let ascii_bytes = || ascii_bytes(ctx.bytes, ctx.descriptor);
+        let charsets = match ctx.options.content_kind {
+            _ if ctx.ebcdic() => Vec::new(),
+            ContentKind::Markup | ContentKind::Html | ContentKind::DelimitedText => Vec::new(),
+            ContentKind::Po => search_po_header(ctx.bytes)
+                .map(normalise)
+                .into_iter()
+                .collect(),
+            ContentKind::VCard => search_vcard_charsets(&ascii_bytes())
+                .into_iter()
+                .map(normalise)
+                .collect(),
+            ContentKind::Ass => search_ass_charset(&ascii_bytes())
+                .map(normalise)
+                .into_iter()
+                .collect(),
+            ContentKind::Rtf => search_rtf_code_page(&ascii_bytes()).into_iter().collect(),
+            ContentKind::Css => search_css_charset(&ascii_bytes()).into_iter().collect(),
+            ContentKind::OfficeXml => {
+                // These parts are always UTF-8, so don't look at anything in the text itself
+                if !ctx.bytes.is_empty() && utf8_prefix_len(ctx.bytes, ctx.cut_short()).is_some() {
+                    return vec![Detection {
+                        charset: "utf-8".to_string(),
+                        source: DetectionSource::Format,
+                    }];
+                }
+                Vec::new()
+            }
+        };
+
+        charsets
+            .into_iter()
+            .map(|charset| Detection {
+                charset,
+                source: DetectionSource::Declaration,
+            })
+            .collect()
+    }
+
+    fn apply(&self, ctx: &DetectContext, result: &mut DetectionResult) {
+        // The charsets are already normalised, with code pages left for the consumer to resolve
+        for Detection { charset, source } in self.detect_all(ctx) {
+            for charset in ctx.resolve(charset) {
+                result.push(charset, source);
+            }
+        }
+    }
+}
+
+/// The charsets in the hint supplied by the caller.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HintStage;
+
+impl Stage for HintStage {
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        self.detect_all(ctx).into_iter().next()
+    }
+
+    fn detect_all(&self, ctx: &DetectContext) -> Vec<Detection> {
+        ctx.hints
+            .iter()
+            .map(|hint| Detection {
+                charset: hint.clone(),
+                source: DetectionSource::Hint,
+            })
+            .collect()
+    }
+}
+
+// The non-standard <?charset name?> processing instruction, in lenient mode
+struct ProcessingInstructionStage;
+
+impl Stage for ProcessingInstructionStage {
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        if !(ctx.markup() && ctx.options.lenient) {
+            return None;
+        }
+
+        search_charset_processing_instruction(ctx.text, ctx.descriptor).map(|charset| Detection {
+            charset: normalise(charset),
+            source: DetectionSource::ProcessingInstruction,
+        })
+    }
+
+    fn apply(&self, ctx: &DetectContext, result: &mut DetectionResult) {
+        if let Some(Detection { charset, source }) = self.detect(ctx) {
+            result.push(charset, source);
+        }
+    }
+}
+
+// Warns of a declaration obscured by character references or comments, in strict mode. It finds
+// no charsets.
+struct SuspiciousDeclarationStage;
+
+impl Stage for SuspiciousDeclarationStage {
+    fn detect(&self, _ctx: &DetectContext) -> Option<Detection> {
+        None
+    }
+
+    fn apply(&self, ctx: &DetectContext, result: &mut DetectionResult) {
+        if ctx.markup()
+            && ctx.options.strict
+            && has_obfuscated_declaration(ctx.text, ctx.descriptor)
+        {
+            result
+                .warnings
+                .push(DetectionWarning::SuspiciousDeclaration);
+        }
+    }
+}
+
+// `utf-8` for doubly encoded UTF-8, with heuristics enabled in lenient mode
+struct MojibakeStage;
+
+impl Stage for MojibakeStage {
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        let options = ctx.options;
+        if ctx.ebcdic() || !(options.heuristics && options.lenient) || !has_mojibake(ctx.text) {
+            return None;
+        }
+
+        Some(Detection {
+            charset: "utf-8".to_string(),
+            source: DetectionSource::Mojibake,
+        })
+    }
+
+    fn apply(&self, ctx: &DetectContext, result: &mut DetectionResult) {
+        if let Some(Detection { charset, source }) = self.detect(ctx) {
+            result.warnings.push(DetectionWarning::Mojibake);
+            result.push(charset, source);
+        }
+    }
+}
+
+/// `utf-8` when the text is valid UTF-8 containing multi-byte sequences and the most likely
+/// candidate found by earlier stages is a declaration or hint naming a single byte charset, such
+/// as `iso-8859-1`.
+///
+/// This goes after the other stages, as it moves `utf-8` in front of the candidates they found.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Utf8MultibyteStage;
+
+impl Stage for Utf8MultibyteStage {
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        let first = ctx.detections.first()?;
+        if !((first.source == DetectionSource::Declaration
+            || first.source == DetectionSource::Hint)
+            && multibyte_utf8_outweighs(&first.charset, ctx.bytes, ctx.cut_short()))
+        {
+            return None;
+        }
+
+        Some(Detection {
+            charset: "utf-8".to_string(),
+            source: DetectionSource::Utf8Multibyte,
+        })
+    }
+
+    fn apply(&self, ctx: &DetectContext, result: &mut DetectionResult) {
+        let utf8 = match self.detect(&ctx.with_detections(&result.detections)) {
+            Some(utf8) => utf8,
+            None => return,
+        };
+        let utf8 = match result
+            .detections
+            .iter()
+            .position(|detection| detection.charset == "utf-8")
+        {
+            Some(index) => result.detections.remove(index),
+            None => utf8,
+        };
+        result.detections.insert(0, utf8);
+    }
+}
+
+/// `utf-8` when no earlier stage found a charset and the text is valid UTF-8, other than
+/// nothing but NUL bytes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Utf8FallbackStage;
+
+impl Stage for Utf8FallbackStage {
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        if ctx.ebcdic()
+            || !ctx.detections.is_empty()
+            || is_nul_padding(ctx.bytes)
+            || utf8_prefix_len(ctx.bytes, ctx.cut_short()).is_none()
+        {
+            return None;
+        }
+
+        let source = if ctx.bytes.is_empty() {
+            DetectionSource::EmptyInput
+        } else {
+            DetectionSource::Utf8Fallback
+        };
+        Some(Detection {
+            charset: "utf-8".to_string(),
+            source,
+        })
+    }
+}
+
+// The charsets commonly used for the language of the text, with heuristics enabled, when no
+// earlier stage found a charset. The language is weak evidence, so it's only a tiebreaker.
+struct LanguageStage;
+
+impl Stage for LanguageStage {
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        self.detect_all(ctx).into_iter().next()
+    }
+
+    fn detect_all(&self, ctx: &DetectContext) -> Vec<Detection> {
+        if !(ctx.markup() && ctx.options.heuristics && ctx.detections.is_empty()) {
+            return Vec::new();
+        }
+
+        let ascii_bytes = ascii_bytes(ctx.text, ctx.descriptor);
+        let charsets = search_language(&ascii_bytes)
+            .as_deref()
+            .map_or(&[][..], language_charsets);
+        charsets
+            .iter()
+            .map(|charset| Detection {
+                charset: charset.to_string(),
+                source: DetectionSource::Language,
+            })
+            .collect()
+    }
+}
+
+// The legacy multi-byte charset that undeclared Chinese and Japanese text commonly uses, which the
+// arrangement of its bytes can give away, with heuristics enabled when no earlier stage found a
+// charset
+struct ByteRangesStage;
+
+impl Stage for ByteRangesStage {
+    fn detect(&self, ctx: &DetectContext) -> Option<Detection> {
+        if ctx.ebcdic() || !ctx.options.heuristics || !ctx.detections.is_empty() {
+            return None;
+        }
+
+        guess_multibyte_charset(ctx.text, ctx.cut_short()).map(|charset| Detection {
+            charset: charset.to_string(),
+            source: DetectionSource::ByteRanges,
+        })
+    }
+}
+
+// Demotes a utf-8 declaration that the text doesn't bear out, in strict mode. It finds no
+// charsets.
+struct ContradictedDeclarationStage;
+
+impl Stage for ContradictedDeclarationStage {
+    fn detect(&self, _ctx: &DetectContext) -> Option<Detection> {
+        None
+    }
+
+    fn apply(&self, ctx: &DetectContext, result: &mut DetectionResult) {
+        if !ctx.options.strict || utf8_prefix_len(ctx.bytes, ctx.cut_short()).is_some() {
+            return;
+        }
+
+        let declared_utf8 = result.detections.iter().position(|detection| {
+            detection.source == DetectionSource::Declaration && detection.charset == "utf-8"
+        });
+        if let Some(index) = declared_utf8 {
+            let mut detection = result.detections.remove(index);
+            detection.source = DetectionSource::ContradictedDeclaration;
+            result.detections.push(detection);
+            result
+                .warnings
+                .push(DetectionWarning::ContradictedDeclaration);
+        }
+    }
+}
+
+/// A detector made up of stages that are run in order.
+///
+/// `DetectorChain::default()` is the chain run by [`detect`](fn.detect.html) and the other
+/// detection functions, giving the same candidates as [`detect_detailed`](fn.detect_detailed.html).
+/// As well as the public stages it has stages for what `Options` enables, such as heuristics, which
+/// do nothing with the default options used by a chain. `DetectorChain::new()` starts with no
+/// stages, allowing them to be reordered, left out, or replaced.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::{DetectorChain, HintStage, Utf8FallbackStage, XmlDeclarationStage};
+///
+/// // Prefer the hint over the declaration in the text
+/// let chain = DetectorChain::new()
+///     .stage(HintStage)
+///     .stage(XmlDeclarationStage)
+///     .stage(Utf8FallbackStage);
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>caf\xE9</p>";
+/// let detected_charsets = chain.detect_slice(text, Some("windows-1252".to_string()));
+/// assert_eq!(
+///     detected_charsets.charsets(),
+///     vec!["windows-1252".to_string(), "iso-8859-1".to_string()]
+/// );
+/// ```
+pub struct DetectorChain {
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl DetectorChain {
+    /// Create a chain with no stages.
+    pub fn new() -> Self {
+        DetectorChain { stages: Vec::new() }
+    }
+
+    /// Add `stage` to the end of the chain.
+    pub fn stage<S: Stage + 'static>(mut self, stage: S) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Attempt to detect the character set of the supplied byte stream.
+    ///
+    /// Short or empty streams are not an error. The optional `hint` is as per
+    /// [`detect`](fn.detect.html).
+    #[cfg(feature = "std")]
+    pub fn detect<R: Read>(
+        &self,
+        reader: &mut R,
+        hint: Option<String>,
+    ) -> Result<DetectionResult, io::Error> {
+        let mut buf = [0u8; PREFIX_LEN];
+        let len = read_prefix(reader, &mut buf)?;
+
+        Ok(self.detect_slice(&buf[..len], hint))
+    }
+
+    /// Attempt to detect the character set of the supplied bytes.
+    ///
+    /// Only the bytes in the window of the default options, as per
+    /// [`Options::window_len`](struct.Options.html#method.window_len), are examined.
+    pub fn detect_slice(&self, bytes: &[u8], hint: Option<String>) -> DetectionResult {
+        self.detect_slice_with_options(bytes, hint, &Options::default())
+    }
+
+    /// Attempt to detect the character set of the supplied bytes using the supplied options.
+    ///
+    /// This is the same as [`detect_slice`](#method.detect_slice) with behaviour adjusted by
+    /// `options`, including the size of the window examined.
+    pub fn detect_slice_with_options(
+        &self,
+        bytes: &[u8],
+        hint: Option<String>,
+        options: &Options,
+    ) -> DetectionResult {
+        let bytes = &bytes[..bytes.len().min(options.window_len())];
+        self.run(bytes, hint.as_deref(), options)
+    }
+
+    // Run the stages over the window `bytes` with behaviour adjusted by `options`
+    pub(crate) fn run(
+        &self,
+        bytes: &[u8],
+        hint: Option<&str>,
+        options: &Options,
+    ) -> DetectionResult {
+        let mut descriptor = detect_descriptor(bytes);
+        let hints = hint.map(parse_hint).unwrap_or_default();
+        let markup =
+            !hints.first().is_some_and(|hint| is_ebcdic(hint)) && is_markup(options.content_kind);
+
+        // Look for encoding="", charset="?"?
+        let mut text = bytes;
+        let mut declared = None;
+        if markup {
+            declared = search_declaration(bytes, descriptor.as_ref(), options);
+            if declared.is_none() && options.lenient {
+                if let Some((offset, found_descriptor, found)) =
+                    search_past_leading_junk(bytes, options)
+                {
+                    descriptor = found_descriptor;
+                    text = &bytes[offset..];
+                    declared = Some(found);
+                }
+            }
+        }
+
+        let mut ascii_bytes = ascii_bytes(text, descriptor.as_ref());
+        mask_xml_processing_instructions(&mut ascii_bytes);
+        let ctx = DetectContext {
+            bytes,
+            text,
+            ascii_bytes: &ascii_bytes,
+            hints: &hints,
+            descriptor: descriptor.as_ref(),
+            declared: declared.as_ref(),
+            options,
+            detections: &[],
+        };
+        let mut result = DetectionResult::default();
+        for stage in &self.stages {
+            stage.apply(&ctx, &mut result);
+        }
+
+        if let Some(ref canonicalizer) = options.canonicalizer {
+            for detection in &mut result.detections {
+                detection.charset = canonicalizer.rename(&detection.charset);
+            }
+        }
+
+        result
+    }
+}
+
+impl Default for DetectorChain {
+    fn default() -> Self {
+        DetectorChain::new()
+            .stage(XmlDeclarationStage)
+            .stage(MetaCharsetStage)
+            .stage(ContentKindStage)
+            .stage(HintStage)
+            .stage(ByteOrderMarkStage)
+            .stage(ProcessingInstructionStage)
+            .stage(SuspiciousDeclarationStage)
+            .stage(MojibakeStage)
+            .stage(Utf8FallbackStage)
+            .stage(LanguageStage)
+            .stage(ByteRangesStage)
+            .stage(ContradictedDeclarationStage)
+            .stage(Utf8MultibyteStage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use detect_slice;
+
+    #[test]
+    fn test_default_chain_matches_detect() {
+        let texts: &[&[u8]] = &[
+            b"",
+            b"<p>Hello</p>",
+            b"<p>caf\xE9</p>",
+            "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>café</p>".as_bytes(),
+            b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel></channel>",
+            b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=Shift-JIS\">",
+            b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"utf-8\"?>",
+            b"<?xml version=\"1.0\"?><?xml-stylesheet href=\"a.css\" charset=\"iso-8859-1\"?>",
+            b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00",
+            b"<\x00?\x00x\x00m\x00l\x00 \x00e\x00n\x00c\x00o\x00d\x00i\x00n\x00g\x00=\x00\"\x00u\x00t\x00f\x00-\x001\x006\x00\"\x00",
+            b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"iso-8859-1\"?>",
+            b"<meta charset=\"windows-1251\"><svg encoding=\"utf-8\">",
+        ];
+        let hints = [
+            None,
+            Some("utf-8, iso-8859-1;q=0.5".to_string()),
+            Some("windows-1252".to_string()),
+            Some("UTF-16".to_string()),
+        ];
+
+        let chain = DetectorChain::default();
+        for text in texts {
+            for hint in &hints {
+                assert_eq!(
+                    chain.detect_slice(text, hint.clone()).charsets(),
+                    detect_slice(text, hint.clone()),
+                    "{:?} with hint {:?}",
+                    text,
+                    hint
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_chain_prioritises_hint() {
+        let text = b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=iso-8859-1\">";
+        let hint = Some("windows-1252".to_string());
+
+        assert_eq!(
+            DetectorChain::default()
+                .detect_slice(text, hint.clone())
+                .charsets(),
+            vec!["iso-8859-1".to_string(), "windows-1252".to_string()]
+        );
+
+        let chain = DetectorChain::new()
+            .stage(HintStage)
+            .stage(MetaCharsetStage)
+            .stage(Utf8FallbackStage);
+        let result = chain.detect_slice(text, hint);
+        assert_eq!(
+            result.detections,
+            vec![
+                Detection {
+                    charset: "windows-1252".to_string(),
+                    source: DetectionSource::Hint,
+                },
+                Detection {
+                    charset: "iso-8859-1".to_string(),
+                    source: DetectionSource::Declaration,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_first_declaration_wins() {
+        let chain = DetectorChain::new()
+            .stage(XmlDeclarationStage)
+            .stage(MetaCharsetStage);
+
+        let text = b"<meta charset=\"windows-1251\"><svg encoding=\"utf-8\">";
+        assert_eq!(
+            chain.detect_slice(text, None).charsets(),
+            vec!["windows-1251".to_string()]
+        );
+        let text = b"<?xml version=\"1.0\" encoding=\"iso-8859-1\"?><meta charset=\"utf-8\">";
+        assert_eq!(
+            chain.detect_slice(text, None).charsets(),
+            vec!["iso-8859-1".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_utf8_bom_overrides_declaration() {
+        let text = b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"iso-8859-1\"?>";
+        let result = DetectorChain::default().detect_slice(text, None);
+        assert_eq!(
+            result.detections,
+            vec![Detection {
+                charset: "utf-8".to_string(),
+                source: DetectionSource::ByteOrderMark,
+            }]
+        );
+        assert_eq!(
+            result.warnings,
+            vec![DetectionWarning::ConflictingByteOrderMark]
+        );
+
+        let chain = DetectorChain::new()
+            .stage(XmlDeclarationStage)
+            .stage(MetaCharsetStage);
+        let text = b"\xEF\xBB\xBF<meta charset=\"windows-1252\">";
+        let result = chain.detect_slice(text, None);
+        assert!(result.detections.is_empty());
+        assert_eq!(
+            result.warnings,
+            vec![DetectionWarning::ConflictingByteOrderMark]
+        );
+    }
+
+    #[test]
+    fn test_closure_stage() {
+        let chain = DetectorChain::new()
+            .stage(|ctx: &DetectContext| {
+                if ctx.bytes().starts_with(b"\x1B$B") {
+                    Some(Detection {
+                        charset: "ISO-2022-JP".to_string(),
+                        source: DetectionSource::Format,
+                    })
+                } else {
+                    None
+                }
+            })
+            .stage(Utf8FallbackStage);
+
+        assert_eq!(
+            chain.detect_slice(b"\x1B$B$3$s\x1B(B", None).charsets(),
+            vec!["iso-2022-jp".to_string()]
+        );
+        assert_eq!(
+            chain.detect_slice(b"plain", None).charsets(),
+            vec!["utf-8".to_string()]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! A typed representation of detected charsets.
+
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+#[cfg(feature = "std")]
+use detect;
+use normalise;
+
+/// A detected charset.
+///
+/// The charsets that detection produces from byte order marks and the structure of the text have
+/// their own variants, as do some commonly declared charsets. Any other charset is held in `Other`
+/// by its normalised name. This includes unusual names taken from declarations, and charsets such
+/// as `koi8-r`, `euc-kr` and `big5` that may be suggested by the language of the text or by a
+/// code page hint.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DetectedCharset {
+    /// `utf-8`
+    Utf8,
+    /// `utf-16le`
+    Utf16Le,
+    /// `utf-16be`
+    Utf16Be,
+    /// `utf-16` with the byte order left unresolved, as per
+    /// [`Options::emit_bare_unicode_widths`](struct.Options.html#structfield.emit_bare_unicode_widths)
+    Utf16,
+    /// `ucs-4le`
+    Ucs4Le,
+    /// `ucs-4be`
+    Ucs4Be,
+    /// `utf-32le`, as per
+    /// [`Options::prefer_utf32_labels`](struct.Options.html#structfield.prefer_utf32_labels)
+    Utf32Le,
+    /// `utf-32be`, as per
+    /// [`Options::prefer_utf32_labels`](struct.Options.html#structfield.prefer_utf32_labels)
+    Utf32Be,
+    /// `utf-32` with the byte order left unresolved, as per
+    /// [`Options::emit_bare_unicode_widths`](struct.Options.html#structfield.emit_bare_unicode_widths)
+    Utf32,
+    /// `utf-7`
+    Utf7,
+    /// `ebcdic`
+    Ebcdic,
+    /// `ascii`
+    Ascii,
+    /// `iso-8859-1`
+    Iso8859_1,
+    /// `windows-1252`
+    Windows1252,
+    /// `shift_jis`
+    ShiftJis,
+    /// `euc-jp`
+    EucJp,
+    /// `gb18030`, which `gbk` and `gb2312` are normalised to
+    Gb18030,
+    /// Any other charset, by its normalised name.
+    Other(String),
+}
+
+/// Another name for [`DetectedCharset`](enum.DetectedCharset.html).
+pub type Encoding = DetectedCharset;
+
+impl DetectedCharset {
+    /// The normalised name of the charset, as returned by [`detect`](fn.detect.html).
+    pub fn name(&self) -> &str {
+        match *self {
+            DetectedCharset::Utf8 => "utf-8",
+            DetectedCharset::Utf16Le => "utf-16le",
+            DetectedCharset::Utf16Be => "utf-16be",
+            DetectedCharset::Utf16 => "utf-16",
+            DetectedCharset::Ucs4Le => "ucs-4le",
+            DetectedCharset::Ucs4Be => "ucs-4be",
+            DetectedCharset::Utf32Le => "utf-32le",
+            DetectedCharset::Utf32Be => "utf-32be",
+            DetectedCharset::Utf32 => "utf-32",
+            DetectedCharset::Utf7 => "utf-7",
+            DetectedCharset::Ebcdic => "ebcdic",
+            DetectedCharset::Ascii => "ascii",
+            DetectedCharset::Iso8859_1 => "iso-8859-1",
+            DetectedCharset::Windows1252 => "windows-1252",
+            DetectedCharset::ShiftJis => "shift_jis",
+            DetectedCharset::EucJp => "euc-jp",
+            DetectedCharset::Gb18030 => "gb18030",
+            DetectedCharset::Other(ref name) => name,
+        }
+    }
+
+    fn from_normalised(name: String) -> Self {
+        match name.as_str() {
+            "utf-8" => DetectedCharset::Utf8,
+            "utf-16le" => DetectedCharset::Utf16Le,
+            "utf-16be" => DetectedCharset::Utf16Be,
+            "utf-16" => DetectedCharset::Utf16,
+            "ucs-4le" => DetectedCharset::Ucs4Le,
+            "ucs-4be" => DetectedCharset::Ucs4Be,
+            "utf-32le" => DetectedCharset::Utf32Le,
+            "utf-32be" => DetectedCharset::Utf32Be,
+            "utf-32" => DetectedCharset::Utf32,
+            "utf-7" => DetectedCharset::Utf7,
+            "ebcdic" => DetectedCharset::Ebcdic,
+            "ascii" => DetectedCharset::Ascii,
+            "iso-8859-1" => DetectedCharset::Iso8859_1,
+            "windows-1252" => DetectedCharset::Windows1252,
+            "shift_jis" => DetectedCharset::ShiftJis,
+            "euc-jp" => DetectedCharset::EucJp,
+            "gb18030" => DetectedCharset::Gb18030,
+            _ => DetectedCharset::Other(name),
+        }
+    }
+}
+
+impl fmt::Display for DetectedCharset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parse a charset name. The name is normalised first, so `UTF8` parses as `Utf8`.
+impl FromStr for DetectedCharset {
+    type Err = Infallible;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(DetectedCharset::from_normalised(normalise(name)))
+    }
+}
+
+/// Attempt to detect the character set of the supplied byte stream, returning typed charsets.
+///
+/// This is the same as [`detect`](fn.detect.html) but with each candidate converted to a
+/// `DetectedCharset`.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::Encoding;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let detected_charsets = xhtmlchardet::detect_typed(&mut text_cursor, None);
+/// assert_eq!(detected_charsets.unwrap_or(vec![]), vec![Encoding::Iso8859_1]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_typed<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<Vec<Encoding>, io::Error> {
+    Ok(detect(reader, hint)?
+        .into_iter()
+        .map(DetectedCharset::from_normalised)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+    #[cfg(feature = "std")]
+    use std::io::Cursor;
+
+    #[test]
+    fn test_round_trip() {
+        let charsets = vec![
+            DetectedCharset::Utf8,
+            DetectedCharset::Utf16Le,
+            DetectedCharset::Utf16Be,
+            DetectedCharset::Utf16,
+            DetectedCharset::Ucs4Le,
+            DetectedCharset::Ucs4Be,
+            DetectedCharset::Utf32Le,
+            DetectedCharset::Utf32Be,
+            DetectedCharset::Utf32,
+            DetectedCharset::Utf7,
+            DetectedCharset::Ebcdic,
+            DetectedCharset::Ascii,
+            DetectedCharset::Iso8859_1,
+            DetectedCharset::Windows1252,
+            DetectedCharset::ShiftJis,
+            DetectedCharset::EucJp,
+            DetectedCharset::Gb18030,
+            DetectedCharset::Other("koi8-r".to_string()),
+        ];
+
+        for charset in charsets {
+            assert_eq!(charset.to_string().parse(), Ok(charset));
+        }
+    }
+
+    #[test]
+    fn test_from_str_normalises() {
+        assert_eq!("UTF8".parse(), Ok(DetectedCharset::Utf8));
+        assert_eq!("Shift-JIS".parse(), Ok(DetectedCharset::ShiftJis));
+        assert_eq!("GB2312".parse(), Ok(DetectedCharset::Gb18030));
+        assert_eq!(
+            "KOI8-R".parse(),
+            Ok(DetectedCharset::Other("koi8-r".to_string()))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_typed_bom() {
+        let mut text_cursor = Cursor::new(b"\xFE\xFF\x00<\x00?\x00x\x00m\x00l".to_vec());
+        let detected_charsets = detect_typed(&mut text_cursor, None).unwrap();
+        assert_eq!(detected_charsets, vec![DetectedCharset::Utf16Be]);
+    }
+}
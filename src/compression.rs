@@ -0,0 +1,70 @@
+//! Detection on compressed byte streams.
+
+use std::io::{self, Read};
+
+#[cfg(feature = "brotli")]
+use brotli::Decompressor;
+#[cfg(feature = "zstd")]
+use zstd;
+
+use detect;
+
+#[cfg(feature = "brotli")]
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// Attempt to detect the character set of the supplied Brotli compressed byte stream.
+///
+/// The stream is decompressed on the fly and only as much of it as `detect` needs is
+/// decompressed.
+///
+/// The optional `hint` is as per [`detect`](fn.detect.html).
+#[cfg(feature = "brotli")]
+pub fn detect_brotli<R: Read>(reader: R, hint: Option<String>) -> Result<Vec<String>, io::Error> {
+    let mut decompressor = Decompressor::new(reader, BROTLI_BUFFER_SIZE);
+    detect(&mut decompressor, hint)
+}
+
+/// Attempt to detect the character set of the supplied Zstandard compressed byte stream.
+///
+/// The stream is decompressed on the fly and only as much of it as `detect` needs is
+/// decompressed.
+///
+/// The optional `hint` is as per [`detect`](fn.detect.html).
+#[cfg(feature = "zstd")]
+pub fn detect_zstd<R: Read>(reader: R, hint: Option<String>) -> Result<Vec<String>, io::Error> {
+    let mut decoder = zstd::stream::read::Decoder::new(reader)?;
+    detect(&mut decoder, hint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const XML: &[u8] =
+        b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn test_detect_brotli() {
+        use std::io::Write;
+
+        let mut compressed = Vec::new();
+        {
+            let mut writer = ::brotli::CompressorWriter::new(&mut compressed, 4096, 9, 22);
+            writer.write_all(XML).unwrap();
+        }
+
+        let detected_charsets = detect_brotli(Cursor::new(compressed), None).unwrap();
+        assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_detect_zstd() {
+        let compressed = zstd::stream::encode_all(XML, 0).unwrap();
+
+        let detected_charsets = detect_zstd(Cursor::new(compressed), None).unwrap();
+        assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
+    }
+}
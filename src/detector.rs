@@ -0,0 +1,229 @@
+//! Reusable detection with options configured once.
+
+#[cfg(feature = "std")]
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+#[cfg(feature = "std")]
+use {detect_detailed_with_options, detect_with_options, DetectionResult};
+use {Canonicalizer, ContentKind, Options};
+
+/// A detector holding the options to detect many texts with.
+///
+/// This saves passing the same `Options` to [`detect_with_options`](fn.detect_with_options.html)
+/// at every call site. Build one with [`Detector::builder`](#method.builder). The default
+/// detector behaves the same as [`detect`](fn.detect.html).
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::Detector;
+///
+/// let detector = Detector::builder()
+///     .scan_limit(1024)
+///     .enable_heuristics(true)
+///     .prefer_utf32_labels(true)
+///     .build();
+/// let text = b"\xFF\xFE\x00\x00<\x00\x00\x00?\x00\x00\x00";
+/// let detected_charsets = detector.detect(&mut Cursor::new(&text[..]), None);
+/// assert_eq!(detected_charsets.unwrap(), vec!["utf-32le".to_string()]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Detector {
+    options: Options,
+}
+
+impl Detector {
+    /// Create a detector with the default options.
+    pub fn new() -> Self {
+        Detector::default()
+    }
+
+    /// Create a detector with the supplied options.
+    pub fn with_options(options: Options) -> Self {
+        Detector { options }
+    }
+
+    /// Start building a detector from the default options.
+    pub fn builder() -> DetectorBuilder {
+        DetectorBuilder::default()
+    }
+
+    /// The options used for detection.
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Attempt to detect the character set of the supplied byte stream.
+    ///
+    /// This is the same as [`detect_with_options`](fn.detect_with_options.html) with the options
+    /// of this detector.
+    #[cfg(feature = "std")]
+    pub fn detect<R: Read>(
+        &self,
+        reader: &mut R,
+        hint: Option<String>,
+    ) -> Result<Vec<String>, io::Error> {
+        detect_with_options(reader, hint, &self.options)
+    }
+
+    /// Attempt to detect the character set of the supplied byte stream, reporting where each
+    /// candidate came from.
+    ///
+    /// This is the same as
+    /// [`detect_detailed_with_options`](fn.detect_detailed_with_options.html) with the options of
+    /// this detector.
+    #[cfg(feature = "std")]
+    pub fn detect_detailed<R: Read>(
+        &self,
+        reader: &mut R,
+        hint: Option<String>,
+    ) -> Result<DetectionResult, io::Error> {
+        detect_detailed_with_options(reader, hint, &self.options)
+    }
+}
+
+/// A builder for a [`Detector`](struct.Detector.html).
+///
+/// Each method sets the `Options` field of the same name.
+#[derive(Clone, Debug, Default)]
+pub struct DetectorBuilder {
+    options: Options,
+}
+
+impl DetectorBuilder {
+    /// Set `Options::lenient`.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.options.lenient = lenient;
+        self
+    }
+
+    /// Set `Options::strict`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    /// Set `Options::content_kind`.
+    pub fn content_kind(mut self, content_kind: ContentKind) -> Self {
+        self.options.content_kind = content_kind;
+        self
+    }
+
+    /// Set `Options::max_total_read`.
+    pub fn max_total_read(mut self, max_total_read: usize) -> Self {
+        self.options.max_total_read = Some(max_total_read);
+        self
+    }
+
+    /// Set `Options::emit_bare_unicode_widths`.
+    pub fn emit_bare_unicode_widths(mut self, emit_bare_unicode_widths: bool) -> Self {
+        self.options.emit_bare_unicode_widths = emit_bare_unicode_widths;
+        self
+    }
+
+    /// Set `Options::heuristics`.
+    pub fn enable_heuristics(mut self, heuristics: bool) -> Self {
+        self.options.heuristics = heuristics;
+        self
+    }
+
+    /// Set `Options::record_agreeing_bom`.
+    pub fn record_agreeing_bom(mut self, record_agreeing_bom: bool) -> Self {
+        self.options.record_agreeing_bom = record_agreeing_bom;
+        self
+    }
+
+    /// Set `Options::scan_limit`.
+    pub fn scan_limit(mut self, scan_limit: usize) -> Self {
+        self.options.scan_limit = scan_limit;
+        self
+    }
+
+    /// Set `Options::canonicalizer`.
+    pub fn canonicalizer(mut self, canonicalizer: Canonicalizer) -> Self {
+        self.options.canonicalizer = Some(canonicalizer);
+        self
+    }
+
+    /// Set `Options::prefer_utf32_labels`.
+    pub fn prefer_utf32_labels(mut self, prefer_utf32_labels: bool) -> Self {
+        self.options.prefer_utf32_labels = prefer_utf32_labels;
+        self
+    }
+
+    /// Set `Options::latin1_as_windows1252`.
+    pub fn latin1_as_windows1252(mut self, latin1_as_windows1252: bool) -> Self {
+        self.options.latin1_as_windows1252 = latin1_as_windows1252;
+        self
+    }
+
+    /// Build the detector.
+    pub fn build(self) -> Detector {
+        Detector {
+            options: self.options,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use std::io::Cursor;
+
+    #[test]
+    fn test_default_detector() {
+        assert_eq!(*Detector::new().options(), Options::default());
+        assert_eq!(Detector::builder().build(), Detector::default());
+    }
+
+    #[test]
+    fn test_builder() {
+        let detector = Detector::builder()
+            .lenient(true)
+            .strict(true)
+            .content_kind(ContentKind::Html)
+            .max_total_read(100)
+            .enable_heuristics(true)
+            .scan_limit(2048)
+            .build();
+        let expected = Options {
+            lenient: true,
+            strict: true,
+            content_kind: ContentKind::Html,
+            max_total_read: Some(100),
+            heuristics: true,
+            scan_limit: 2048,
+            ..Options::default()
+        };
+        assert_eq!(*detector.options(), expected);
+        assert_eq!(Detector::with_options(expected), detector);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect() {
+        let text = b"<meta charset=\"iso-8859-1\"><p>\x93Hello\x94</p>";
+        let detector = Detector::builder().latin1_as_windows1252(true).build();
+        for _ in 0..2 {
+            assert_eq!(
+                detector.detect(&mut Cursor::new(&text[..]), None).unwrap(),
+                vec!["windows-1252".to_string()]
+            );
+        }
+        assert_eq!(
+            detector
+                .detect_detailed(&mut Cursor::new(&text[..]), None)
+                .unwrap()
+                .charsets(),
+            vec!["windows-1252".to_string()]
+        );
+    }
+}
@@ -0,0 +1,566 @@
+//! Bridging between the charset names produced by this crate and the `encoding_rs` crate.
+
+use std::io::{self, Read};
+
+use encoding_rs::*;
+
+use {detect, detect_slice, normalise, read_prefix, PREFIX_LEN};
+
+const TRANSCODING_BUFFER_SIZE: usize = 4096;
+
+/// Attempt to detect the character set of the supplied byte stream, pairing each candidate
+/// with the name of the `encoding_rs` encoding it resolves to.
+///
+/// The names returned by [`detect`](fn.detect.html) don't always match those used by
+/// `encoding_rs`. For example `iso-8859-1` is treated as `windows-1252` by `encoding_rs`, and
+/// `ucs-4le` has no `encoding_rs` equivalent at all, in which case the label is `None`.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let detected_charsets = xhtmlchardet::detect_with_encoding_rs_label(&mut text_cursor, None);
+/// assert_eq!(
+///     detected_charsets.unwrap_or(vec![]),
+///     vec![("iso-8859-1".to_string(), Some("windows-1252"))]
+/// );
+/// ```
+pub fn detect_with_encoding_rs_label<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<Vec<(String, Option<&'static str>)>, io::Error> {
+    let candidates = detect(reader, hint)?;
+
+    Ok(candidates
+        .into_iter()
+        .map(|charset| {
+            let label = encoding_rs_label(&charset);
+            (charset, label)
+        })
+        .collect())
+}
+
+/// Attempt to detect the character set of the supplied byte stream as an `encoding_rs` encoding,
+/// ready to decode the text with.
+///
+/// The first candidate returned by [`detect`](fn.detect.html) that `encoding_rs` supports is used.
+/// Some candidates, such as `ucs-4le` from a UCS-4 byte order mark, have no `encoding_rs`
+/// equivalent and are skipped. `None` is returned if no candidate is supported.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate encoding_rs;
+/// extern crate xhtmlchardet;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>caf\xE9</p>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let encoding = xhtmlchardet::detect_encoding_rs(&mut text_cursor, None).unwrap();
+/// assert_eq!(encoding, Some(encoding_rs::WINDOWS_1252));
+/// ```
+pub fn detect_encoding_rs<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<Option<&'static Encoding>, io::Error> {
+    Ok(detect(reader, hint)?
+        .iter()
+        .filter_map(|charset| Encoding::for_label(charset.as_bytes()))
+        .next())
+}
+
+/// Decode `bytes` in the named charset to a UTF-8 `String`.
+///
+/// A byte order mark matching the charset is removed. Malformed sequences are replaced with
+/// U+FFFD REPLACEMENT CHARACTER. `None` is returned if `encoding_rs` doesn't support the
+/// charset.
+///
+/// Charsets that the Encoding Standard maps to the `replacement` encoding, such as
+/// `hz-gb-2312`, are never really decoded. Non-empty input decodes to a single U+FFFD, as per
+/// the standard.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// let decoded = xhtmlchardet::decode(b"caf\xE9", "iso-8859-1");
+/// assert_eq!(decoded, Some("café".to_string()));
+/// ```
+pub fn decode(bytes: &[u8], charset: &str) -> Option<String> {
+    let encoding = Encoding::for_label(charset.as_bytes())?;
+
+    // Decoding these is unsafe, so the whole input becomes a single replacement character
+    if encoding == REPLACEMENT {
+        return Some(if bytes.is_empty() {
+            String::new()
+        } else {
+            "\u{FFFD}".to_string()
+        });
+    }
+
+    let (text, _had_errors) = encoding.decode_with_bom_removal(bytes);
+    Some(text.into_owned())
+}
+
+/// Determine whether `bytes` decode without errors in the named charset.
+///
+/// This can be used to confirm a detected candidate before committing to it. A byte order mark
+/// matching the charset is ignored. All of `bytes` is decoded, so a sample that ends part way
+/// through a multi-byte sequence isn't decodable. `false` is returned if `encoding_rs` doesn't
+/// support the charset.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// assert!(xhtmlchardet::is_decodable_as(b"caf\xC3\xA9", "utf-8"));
+/// assert!(!xhtmlchardet::is_decodable_as(b"caf\xE9", "utf-8"));
+/// ```
+pub fn is_decodable_as(bytes: &[u8], charset: &str) -> bool {
+    let encoding = match Encoding::for_label(charset.as_bytes()) {
+        Some(encoding) => encoding,
+        None => return false,
+    };
+
+    decodes_without_errors(bytes, encoding)
+}
+
+/// List every charset that `bytes` decode without errors in.
+///
+/// This is meant for diagnostics, such as letting a person choose when detection is ambiguous,
+/// and is expensive as `bytes` are decoded in turn with each of the charsets supported by
+/// `encoding_rs`. The charsets are ranked by how telling it is that `bytes` decode in them: UTF-8
+/// first, then the multi-byte legacy charsets, then single byte charsets, which most text decodes
+/// in, and finally UTF-16, which is unlikely without a byte order mark. Decoding is as per
+/// [`is_decodable_as`](fn.is_decodable_as.html).
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// let candidates = xhtmlchardet::candidate_encodings(b"<p>caf\xE9</p>");
+/// assert!(!candidates.contains(&"utf-8".to_string()));
+/// assert!(candidates.contains(&"windows-1252".to_string()));
+/// ```
+pub fn candidate_encodings(bytes: &[u8]) -> Vec<String> {
+    let encodings = [
+        UTF_8,
+        SHIFT_JIS,
+        EUC_JP,
+        ISO_2022_JP,
+        EUC_KR,
+        GB18030,
+        BIG5,
+        WINDOWS_1252,
+        WINDOWS_1250,
+        WINDOWS_1251,
+        WINDOWS_1253,
+        WINDOWS_1254,
+        WINDOWS_1255,
+        WINDOWS_1256,
+        WINDOWS_1257,
+        WINDOWS_1258,
+        WINDOWS_874,
+        ISO_8859_2,
+        ISO_8859_3,
+        ISO_8859_4,
+        ISO_8859_5,
+        ISO_8859_6,
+        ISO_8859_7,
+        ISO_8859_8,
+        ISO_8859_8_I,
+        ISO_8859_10,
+        ISO_8859_13,
+        ISO_8859_14,
+        ISO_8859_15,
+        ISO_8859_16,
+        KOI8_R,
+        KOI8_U,
+        IBM866,
+        MACINTOSH,
+        X_MAC_CYRILLIC,
+        UTF_16LE,
+        UTF_16BE,
+    ];
+
+    encodings
+        .iter()
+        .filter(|&&encoding| decodes_without_errors(bytes, encoding))
+        .map(|encoding| normalise(encoding.name()))
+        .collect()
+}
+
+/// Detect the charset of `bytes` and decode them to a UTF-8 `String`.
+///
+/// The first detected candidate supported by `encoding_rs` is used to decode the text, as per
+/// [`decode`](fn.decode.html). The optional `hint` is as per [`detect`](fn.detect.html). `None` is
+/// returned if no candidate is supported.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>caf\xE9</p>";
+/// let transcoded = xhtmlchardet::transcode_to_utf8(text, None);
+/// assert_eq!(
+///     transcoded,
+///     Some("<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>café</p>".to_string())
+/// );
+/// ```
+pub fn transcode_to_utf8(bytes: &[u8], hint: Option<String>) -> Option<String> {
+    detect_slice(bytes, hint)
+        .iter()
+        .filter_map(|charset| decode(bytes, charset))
+        .next()
+}
+
+/// A reader that detects the charset of the text read from another reader, and transcodes it to
+/// UTF-8.
+///
+/// The charset is detected from the start of the text, as per
+/// [`transcode_to_utf8`](fn.transcode_to_utf8.html), when the reader is created. The text is then
+/// decoded incrementally as it's read. A byte order mark is removed. Malformed sequences are
+/// replaced with U+FFFD REPLACEMENT CHARACTER. Text with no candidate supported by `encoding_rs`
+/// is decoded as UTF-8.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::{Cursor, Read};
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::Utf8TranscodingReader;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>caf\xE9</p>";
+/// let mut reader = Utf8TranscodingReader::new(Cursor::new(&text[..]), None).unwrap();
+/// let mut transcoded = String::new();
+/// reader.read_to_string(&mut transcoded).unwrap();
+/// assert_eq!(transcoded, "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>café</p>");
+/// ```
+pub struct Utf8TranscodingReader<R> {
+    inner: R,
+    decoder: Decoder,
+    encoding: &'static Encoding,
+    input: Vec<u8>,
+    input_pos: usize,
+    output: Vec<u8>,
+    output_pos: usize,
+    eof: bool,
+    finished: bool,
+}
+
+impl<R: Read> Utf8TranscodingReader<R> {
+    /// Create a reader that transcodes the text read from `inner` to UTF-8.
+    ///
+    /// The start of `inner` is read in order to detect the charset. The optional `hint` is as per
+    /// [`detect`](fn.detect.html).
+    pub fn new(mut inner: R, hint: Option<String>) -> Result<Self, io::Error> {
+        let mut input = vec![0u8; PREFIX_LEN];
+        let len = read_prefix(&mut inner, &mut input)?;
+        input.truncate(len);
+
+        let encoding = detect_slice(&input, hint)
+            .iter()
+            .filter_map(|charset| Encoding::for_label(charset.as_bytes()))
+            .next()
+            .unwrap_or(UTF_8);
+
+        Ok(Utf8TranscodingReader {
+            inner,
+            decoder: encoding.new_decoder_with_bom_removal(),
+            encoding,
+            input,
+            input_pos: 0,
+            output: Vec::with_capacity(TRANSCODING_BUFFER_SIZE),
+            output_pos: 0,
+            eof: len < PREFIX_LEN,
+            finished: false,
+        })
+    }
+
+    /// The name of the `encoding_rs` encoding the text is being decoded from.
+    pub fn encoding_name(&self) -> &'static str {
+        self.encoding.name()
+    }
+
+    /// Unwrap this reader, returning the underlying reader.
+    ///
+    /// Any text that has been read from the underlying reader but not yet transcoded is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn fill_input(&mut self) -> Result<(), io::Error> {
+        self.input.resize(TRANSCODING_BUFFER_SIZE, 0);
+        self.input_pos = 0;
+        loop {
+            match self.inner.read(&mut self.input) {
+                Ok(n) => {
+                    self.input.truncate(n);
+                    self.eof = n == 0;
+                    return Ok(());
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {} // retry
+                Err(err) => {
+                    self.input.clear();
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for Utf8TranscodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.output_pos < self.output.len() {
+                let pending = &self.output[self.output_pos..];
+                let len = pending.len().min(buf.len());
+                buf[..len].copy_from_slice(&pending[..len]);
+                self.output_pos += len;
+                return Ok(len);
+            }
+
+            if self.finished || buf.is_empty() {
+                return Ok(0);
+            }
+
+            if self.input_pos == self.input.len() && !self.eof {
+                self.fill_input()?;
+            }
+
+            // Decode into an internal buffer as the decoder may not be able to make progress
+            // when given very small output buffers
+            self.output.resize(TRANSCODING_BUFFER_SIZE, 0);
+            let (result, read, written, _) = self.decoder.decode_to_utf8(
+                &self.input[self.input_pos..],
+                &mut self.output,
+                self.eof,
+            );
+            self.input_pos += read;
+            self.output.truncate(written);
+            self.output_pos = 0;
+
+            if self.eof && result == CoderResult::InputEmpty {
+                self.finished = true;
+            }
+        }
+    }
+}
+
+// Whether `bytes` decode without errors in `encoding`, ignoring a matching byte order mark
+fn decodes_without_errors(bytes: &[u8], encoding: &'static Encoding) -> bool {
+    // Nothing but empty input decodes without error in the replacement encoding
+    if encoding == REPLACEMENT {
+        return bytes.is_empty();
+    }
+
+    let bytes = match Encoding::for_bom(bytes) {
+        Some((bom_encoding, bom_length)) if bom_encoding == encoding => &bytes[bom_length..],
+        _ => bytes,
+    };
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .is_some()
+}
+
+fn encoding_rs_label(charset: &str) -> Option<&'static str> {
+    Encoding::for_label(charset.as_bytes()).map(Encoding::name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_encoding_rs_label() {
+        assert_eq!(encoding_rs_label("utf-8"), Some("UTF-8"));
+        assert_eq!(encoding_rs_label("utf-16le"), Some("UTF-16LE"));
+        assert_eq!(encoding_rs_label("shift_jis"), Some("Shift_JIS"));
+        assert_eq!(encoding_rs_label("ucs-4le"), None);
+        assert_eq!(encoding_rs_label("ebcdic"), None);
+    }
+
+    #[test]
+    fn test_detect_with_encoding_rs_label_bom() {
+        let mut text_cursor = Cursor::new(b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00".to_vec());
+        let detected_charsets = detect_with_encoding_rs_label(&mut text_cursor, None).unwrap();
+        assert_eq!(
+            detected_charsets,
+            vec![("utf-16le".to_string(), Some("UTF-16LE"))]
+        );
+    }
+
+    #[test]
+    fn test_detect_encoding_rs() {
+        let mut text_cursor = Cursor::new(b"\xFE\xFF\x00<\x00p\x00>".to_vec());
+        assert_eq!(
+            detect_encoding_rs(&mut text_cursor, None).unwrap(),
+            Some(UTF_16BE)
+        );
+
+        // UCS-4 isn't supported so the hint is used instead
+        let mut text_cursor = Cursor::new(b"\xFF\xFE\x00\x00<\x00\x00\x00".to_vec());
+        assert_eq!(
+            detect_encoding_rs(&mut text_cursor, Some("utf-8".to_string())).unwrap(),
+            Some(UTF_8)
+        );
+        let mut text_cursor = Cursor::new(b"\xFF\xFE\x00\x00<\x00\x00\x00".to_vec());
+        assert_eq!(detect_encoding_rs(&mut text_cursor, None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_decode_replacement() {
+        assert_eq!(
+            decode(b"~{<:Ky2;S{#,~}", "hz-gb-2312"),
+            Some("\u{FFFD}".to_string())
+        );
+        assert_eq!(decode(b"", "hz-gb-2312"), Some(String::new()));
+    }
+
+    #[test]
+    fn test_decode_unknown() {
+        assert_eq!(decode(b"text", "ucs-4le"), None);
+    }
+
+    #[test]
+    fn test_is_decodable_as_utf8() {
+        assert!(is_decodable_as("<p>café</p>".as_bytes(), "utf-8"));
+        assert!(is_decodable_as(b"\xEF\xBB\xBF<p>Hello</p>", "UTF8"));
+        assert!(!is_decodable_as(b"<p>caf\xE9</p>", "utf-8"));
+        // Truncated part way through a sequence
+        assert!(!is_decodable_as(b"<p>caf\xC3", "utf-8"));
+    }
+
+    #[test]
+    fn test_is_decodable_as_windows_1252() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert!(is_decodable_as(&bytes, "windows-1252"));
+        assert!(is_decodable_as(&bytes, "iso-8859-1"));
+    }
+
+    #[test]
+    fn test_is_decodable_as_utf16() {
+        assert!(is_decodable_as(b"\xFF\xFE<\x00p\x00>\x00", "utf-16le"));
+        // Unpaired surrogate
+        assert!(!is_decodable_as(b"\x00\xD8<\x00", "utf-16le"));
+    }
+
+    #[test]
+    fn test_is_decodable_as_unknown() {
+        assert!(!is_decodable_as(b"text", "ucs-4le"));
+        assert!(!is_decodable_as(b"text", "hz-gb-2312"));
+        assert!(is_decodable_as(b"", "hz-gb-2312"));
+    }
+
+    #[test]
+    fn test_candidate_encodings_ascii() {
+        let candidates = candidate_encodings(b"<p>Hello</p>");
+        assert!(candidates.len() > 30);
+        assert_eq!(candidates[0], "utf-8");
+        for charset in &[
+            "shift_jis",
+            "windows-1252",
+            "iso-8859-2",
+            "koi8-r",
+            "utf-16le",
+        ] {
+            assert!(candidates.contains(&charset.to_string()), "{}", charset);
+        }
+    }
+
+    #[test]
+    fn test_candidate_encodings_invalid_utf8() {
+        let candidates = candidate_encodings(b"<p>caf\xE9</p>");
+        assert!(!candidates.contains(&"utf-8".to_string()));
+        assert!(candidates.contains(&"windows-1252".to_string()));
+        assert!(candidates.contains(&"iso-8859-15".to_string()));
+        // Odd length
+        assert!(!candidates.contains(&"utf-16le".to_string()));
+    }
+
+    // A reader that returns at most one byte per read
+    struct ByteReader<R>(R);
+
+    impl<R: Read> Read for ByteReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_utf8_transcoding_reader_windows_1252() {
+        let mut source = b"<?xml version=\"1.0\" encoding=\"windows-1252\"?>\n<feed>\n".to_vec();
+        let mut expected = String::from_utf8(source.clone()).unwrap();
+        for _ in 0..1000 {
+            source.extend_from_slice(b"<entry>caf\xE9 \x80 \x93quoted\x94</entry>\n");
+            expected.push_str("<entry>café € “quoted”</entry>\n");
+        }
+        source.extend_from_slice(b"</feed>\n");
+        expected.push_str("</feed>\n");
+
+        let mut reader = Utf8TranscodingReader::new(Cursor::new(&source), None).unwrap();
+        assert_eq!(reader.encoding_name(), "windows-1252");
+        let mut transcoded = String::new();
+        reader.read_to_string(&mut transcoded).unwrap();
+        assert_eq!(transcoded, expected);
+
+        let mut reader =
+            Utf8TranscodingReader::new(ByteReader(Cursor::new(&source)), None).unwrap();
+        let mut transcoded = Vec::new();
+        let mut buf = [0u8; 1];
+        while reader.read(&mut buf).unwrap() == 1 {
+            transcoded.push(buf[0]);
+        }
+        assert_eq!(String::from_utf8(transcoded).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_utf8_transcoding_reader_strips_bom() {
+        let text = b"\xFF\xFE<\x00p\x00>\x00\xE9\x00";
+        let mut reader = Utf8TranscodingReader::new(Cursor::new(&text[..]), None).unwrap();
+        let mut transcoded = String::new();
+        reader.read_to_string(&mut transcoded).unwrap();
+        assert_eq!(transcoded, "<p>é");
+    }
+
+    #[test]
+    fn test_utf8_transcoding_reader_empty() {
+        let mut reader = Utf8TranscodingReader::new(Cursor::new(&b""[..]), None).unwrap();
+        let mut transcoded = String::new();
+        reader.read_to_string(&mut transcoded).unwrap();
+        assert_eq!(transcoded, "");
+    }
+
+    #[test]
+    fn test_utf8_transcoding_reader_undetectable() {
+        // No candidates at all, so it's decoded as UTF-8 with replacement
+        let text = b"caf\xE9";
+        let mut reader = Utf8TranscodingReader::new(Cursor::new(&text[..]), None).unwrap();
+        let mut transcoded = String::new();
+        reader.read_to_string(&mut transcoded).unwrap();
+        assert_eq!(transcoded, "caf\u{FFFD}");
+    }
+
+    #[test]
+    fn test_transcode_to_utf8_replacement() {
+        let text = b"<?xml version=\"1.0\" encoding=\"HZ-GB-2312\"?><p>~{<:Ky2;S{#,~}</p>";
+        assert_eq!(transcode_to_utf8(text, None), Some("\u{FFFD}".to_string()));
+    }
+
+    #[test]
+    fn test_transcode_to_utf8_bom() {
+        let text = b"\xFF\xFE<\x00p\x00>\x00";
+        assert_eq!(transcode_to_utf8(text, None), Some("<p>".to_string()));
+    }
+}
@@ -0,0 +1,191 @@
+//! Detection that reports why it failed with a typed error.
+
+use std::error;
+use std::fmt;
+use std::io::{self, Read};
+
+use {detect_window, read_stream_window, DetectionSource, DetectionWarning, Options};
+
+/// The reasons detection with [`try_detect`](fn.try_detect.html) can fail.
+#[derive(Debug)]
+pub enum DetectError {
+    /// Reading from the stream failed.
+    Io(io::Error),
+    /// The stream was empty.
+    Empty,
+    /// The stream ended before the first four bytes, which are required to detect the charset.
+    TooShort,
+    /// The byte order mark is for a different encoding form from the declaration, such as a
+    /// UTF-16 byte order mark with `encoding="iso-8859-1"`, as per
+    /// `DetectionWarning::ConflictingByteOrderMark`. A UTF-8 byte order mark is authoritative,
+    /// so it never conflicts.
+    Conflict {
+        /// The charset indicated by the byte order mark.
+        bom: String,
+        /// The charset of the declaration.
+        declared: String,
+    },
+}
+
+impl fmt::Display for DetectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DetectError::Io(ref err) => write!(f, "failed to read the text: {}", err),
+            DetectError::Empty => f.write_str("the text is empty"),
+            DetectError::TooShort => f.write_str("the text is shorter than four bytes"),
+            DetectError::Conflict {
+                ref bom,
+                ref declared,
+            } => write!(
+                f,
+                "byte order mark for {} conflicts with the declared encoding {}",
+                bom, declared
+            ),
+        }
+    }
+}
+
+impl error::Error for DetectError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            DetectError::Io(ref err) => Some(err),
+            DetectError::Empty | DetectError::TooShort | DetectError::Conflict { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for DetectError {
+    fn from(err: io::Error) -> Self {
+        DetectError::Io(err)
+    }
+}
+
+/// Attempt to detect the character set of the supplied byte stream, reporting failure with a
+/// [`DetectError`](enum.DetectError.html).
+///
+/// This is the same as [`detect_strict`](fn.detect_strict.html), except that an empty stream is
+/// reported as `DetectError::Empty`, one shorter than four bytes as `DetectError::TooShort` and a
+/// byte order mark that conflicts with the declaration as `DetectError::Conflict`.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::DetectError;
+///
+/// let text = b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00 \x00e\x00n\x00c\x00o\x00d\x00i\x00n\x00g\x00=\x00\"\x00u\x00t\x00f\x00-\x008\x00\"\x00";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// match xhtmlchardet::try_detect(&mut text_cursor, None) {
+///     Err(DetectError::Conflict { bom, declared }) => {
+///         assert_eq!(bom, "utf-16le");
+///         assert_eq!(declared, "utf-8");
+///     }
+///     other => panic!("unexpected result {:?}", other),
+/// }
+/// ```
+pub fn try_detect<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<Vec<String>, DetectError> {
+    let options = Options {
+        strict: true,
+        ..Options::default()
+    };
+    let bytes = read_stream_window(reader, &options)?;
+    let result = match detect_window(&bytes, hint.as_deref(), &options) {
+        Ok(Some(result)) => result,
+        Ok(None) if bytes.is_empty() => return Err(DetectError::Empty),
+        Ok(None) => return Ok(Vec::new()),
+        Err(_) => return Err(DetectError::TooShort),
+    };
+
+    // A UTF-8 byte order mark leaves the declaration out of the candidates, so there's only a
+    // conflict to report when the declaration is among them
+    if result
+        .warnings
+        .contains(&DetectionWarning::ConflictingByteOrderMark)
+    {
+        let charset = |sources: &[DetectionSource]| {
+            result
+                .detections
+                .iter()
+                .find(|detection| sources.contains(&detection.source))
+                .map(|detection| detection.charset.clone())
+        };
+        let declared = charset(&[
+            DetectionSource::Declaration,
+            DetectionSource::ContradictedDeclaration,
+        ]);
+        if let (Some(bom), Some(declared)) = (charset(&[DetectionSource::ByteOrderMark]), declared)
+        {
+            return Err(DetectError::Conflict { bom, declared });
+        }
+    }
+
+    Ok(result.charsets())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+    use std::io::Cursor;
+    use tests::utf16le;
+
+    #[test]
+    fn test_try_detect() {
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel></channel>";
+        let detected = try_detect(&mut Cursor::new(&text[..]), None).unwrap();
+        assert_eq!(detected, vec!["iso-8859-1".to_string()]);
+
+        let text = utf16le("<?xml version=\"1.0\" encoding=\"utf-16\"?>");
+        let detected = try_detect(&mut Cursor::new(text), None).unwrap();
+        assert_eq!(detected, vec!["utf-16le".to_string()]);
+    }
+
+    #[test]
+    fn test_try_detect_empty() {
+        match try_detect(&mut Cursor::new(&b""[..]), None) {
+            Err(DetectError::Empty) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_try_detect_short() {
+        match try_detect(&mut Cursor::new(&b"<p"[..]), None) {
+            Err(DetectError::TooShort) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(try_detect(&mut Cursor::new(&b"<p>H"[..]), None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_try_detect_conflict() {
+        let text = utf16le("<?xml version=\"1.0\" encoding=\"iso-8859-1\"?>");
+        match try_detect(&mut Cursor::new(text), None) {
+            Err(DetectError::Conflict { bom, declared }) => {
+                assert_eq!(bom, "utf-16le");
+                assert_eq!(declared, "iso-8859-1");
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // A UTF-8 byte order mark is authoritative
+        let text = b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"iso-8859-1\"?>";
+        let detected = try_detect(&mut Cursor::new(&text[..]), None).unwrap();
+        assert_eq!(detected, vec!["utf-8".to_string()]);
+    }
+
+    #[test]
+    fn test_detect_error_source() {
+        let err = DetectError::from(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"));
+        assert!(err.source().is_some());
+        assert_eq!(err.to_string(), "failed to read the text: broken pipe");
+        assert!(DetectError::Empty.source().is_none());
+    }
+}
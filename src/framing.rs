@@ -0,0 +1,162 @@
+//! Detection on text fields embedded in binary protocols.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use detect_slice;
+
+/// The width of the unsigned integer that a length prefix is stored as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LengthWidth {
+    /// A one byte length.
+    U8,
+    /// A two byte length.
+    U16,
+    /// A four byte length.
+    U32,
+    /// An eight byte length.
+    U64,
+}
+
+impl LengthWidth {
+    /// The number of bytes the length takes up.
+    pub fn bytes(self) -> usize {
+        match self {
+            LengthWidth::U8 => 1,
+            LengthWidth::U16 => 2,
+            LengthWidth::U32 => 4,
+            LengthWidth::U64 => 8,
+        }
+    }
+}
+
+/// How a text field is framed within the surrounding bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Framing {
+    /// The text is preceded by its length in bytes, as a big endian unsigned integer.
+    LengthPrefixBigEndian(LengthWidth),
+    /// The text is preceded by its length in bytes, as a little endian unsigned integer.
+    LengthPrefixLittleEndian(LengthWidth),
+    /// The text is terminated by a NUL byte.
+    NulTerminated,
+}
+
+impl Framing {
+    // Pick the text out of `bytes`. A field that claims to be longer than the bytes available is
+    // taken to be truncated.
+    fn payload<'a>(&self, bytes: &'a [u8]) -> &'a [u8] {
+        match *self {
+            Framing::LengthPrefixBigEndian(width) | Framing::LengthPrefixLittleEndian(width) => {
+                let width = width.bytes();
+                if bytes.len() < width {
+                    return &[];
+                }
+
+                let (prefix, rest) = bytes.split_at(width);
+                let fold = |length: u64, &byte: &u8| (length << 8) | u64::from(byte);
+                let length = match *self {
+                    Framing::LengthPrefixBigEndian(_) => prefix.iter().fold(0, fold),
+                    _ => prefix.iter().rev().fold(0, fold),
+                };
+                let length = length.min(rest.len() as u64) as usize;
+                &rest[..length]
+            }
+            Framing::NulTerminated => {
+                let end = bytes
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(bytes.len());
+                &bytes[..end]
+            }
+        }
+    }
+}
+
+/// Attempt to detect the character set of a text field framed by a length prefix or NUL
+/// terminator.
+///
+/// The framing is removed, along with anything following the field, before detecting the charset
+/// of the text as per [`detect_slice`](fn.detect_slice.html).
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::Framing;
+///
+/// let field = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\0\xFF\xFE";
+/// let detected_charsets = xhtmlchardet::detect_field(field, Framing::NulTerminated);
+/// assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
+/// ```
+pub fn detect_field(bytes: &[u8], framing: Framing) -> Vec<String> {
+    detect_slice(framing.payload(bytes), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn test_length_prefixed_utf8_field() {
+        let text = "<p>naïve café</p>".as_bytes();
+        let mut bytes = vec![0, text.len() as u8];
+        bytes.extend_from_slice(text);
+        // The next field isn't valid UTF-8 and declares a charset of its own
+        bytes.extend_from_slice(b"\x00\x30<meta charset=\"shift_jis\"><p>\x82\xA0</p>");
+
+        assert_eq!(
+            detect_field(&bytes, Framing::LengthPrefixBigEndian(LengthWidth::U16)),
+            vec!["utf-8".to_string()]
+        );
+
+        bytes.swap(0, 1);
+        assert_eq!(
+            detect_field(&bytes, Framing::LengthPrefixLittleEndian(LengthWidth::U16)),
+            vec!["utf-8".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_nul_terminated_latin1_field() {
+        let mut bytes = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>caf\xE9</p>\0".to_vec();
+        bytes.extend_from_slice(&[0; 32]);
+
+        assert_eq!(
+            detect_field(&bytes, Framing::NulTerminated),
+            vec!["iso-8859-1".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_payload_truncated() {
+        assert_eq!(
+            Framing::LengthPrefixBigEndian(LengthWidth::U16).payload(b"\x00"),
+            b""
+        );
+        assert_eq!(
+            Framing::LengthPrefixBigEndian(LengthWidth::U16).payload(b"\xFF\xFFabc"),
+            b"abc"
+        );
+        assert_eq!(Framing::NulTerminated.payload(b"abc"), b"abc");
+    }
+
+    #[test]
+    fn test_payload_widths() {
+        let bytes = b"\x00\x00\x00\x00\x00\x00\x00\x03abcdef";
+        assert_eq!(
+            Framing::LengthPrefixBigEndian(LengthWidth::U64).payload(bytes),
+            b"abc"
+        );
+        assert_eq!(
+            Framing::LengthPrefixBigEndian(LengthWidth::U32).payload(bytes),
+            b""
+        );
+        assert_eq!(
+            Framing::LengthPrefixLittleEndian(LengthWidth::U8).payload(b"\x02abc"),
+            b"ab"
+        );
+    }
+}
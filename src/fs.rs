@@ -0,0 +1,81 @@
+//! Detection on files.
+
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use detect;
+
+/// Attempt to detect the character set of the file at `path`.
+///
+/// The optional `hint` is as per [`detect`](fn.detect.html).
+///
+/// ### Example
+///
+/// ```no_run
+/// extern crate xhtmlchardet;
+///
+/// let detected_charsets = xhtmlchardet::detect_from_path("feed.xml", None);
+/// ```
+pub fn detect_from_path<P: AsRef<Path>>(
+    path: P,
+    hint: Option<String>,
+) -> Result<Vec<String>, io::Error> {
+    let mut file = File::open(path)?;
+    detect(&mut file, hint)
+}
+
+/// Attempt to detect the character set of each of the files in `paths`.
+///
+/// Each path is returned alongside the result for that file, in the same order as `paths`. An
+/// error with one file doesn't prevent the others from being detected. When the `rayon` feature
+/// is enabled the files are detected in parallel.
+pub fn detect_paths<P: AsRef<Path> + Sync>(
+    paths: &[P],
+) -> Vec<(PathBuf, Result<Vec<String>, io::Error>)> {
+    #[cfg(feature = "rayon")]
+    let iter = paths.par_iter();
+    #[cfg(not(feature = "rayon"))]
+    let iter = paths.iter();
+
+    iter.map(|path| {
+        let path = path.as_ref();
+        (path.to_path_buf(), detect_from_path(path, None))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_paths() {
+        let paths = [
+            "tests/iso-8859-1-xml-decl.txt",
+            "tests/does-not-exist.txt",
+            "tests/utf-16le-xml-bom-decl-nohint.txt",
+        ];
+        let results = detect_paths(&paths);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0, PathBuf::from(paths[0]));
+        assert_eq!(
+            results[0].1.as_ref().unwrap(),
+            &vec!["iso-8859-1".to_string()]
+        );
+        assert_eq!(results[1].0, PathBuf::from(paths[1]));
+        assert_eq!(
+            results[1].1.as_ref().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(results[2].0, PathBuf::from(paths[2]));
+        assert_eq!(
+            results[2].1.as_ref().unwrap(),
+            &vec!["utf-16le".to_string()]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Resuming detection with more of the text when the first look was inconclusive.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use {detect_core, DetectionResult, DetectionSource, DetectionWarning, Options};
+
+/// The text seen so far and what was found in it, for use with
+/// [`detect_more`](fn.detect_more.html).
+#[derive(Clone, Debug, Default)]
+pub struct DetectState {
+    bytes: Vec<u8>,
+    hint: Option<String>,
+    options: Options,
+    result: DetectionResult,
+}
+
+impl DetectState {
+    /// Create a state for detecting the charset of text with the optional `hint`, as per
+    /// [`detect`](fn.detect.html).
+    pub fn new(hint: Option<String>) -> Self {
+        DetectState::with_options(hint, Options::default())
+    }
+
+    /// Create a state for detecting the charset of text using the supplied options.
+    ///
+    /// As with [`detect_with_options`](fn.detect_with_options.html), no more of the text than
+    /// the window set by `scan_limit` and `content_kind`, or `max_total_read` if that's smaller, is
+    /// examined.
+    pub fn with_options(hint: Option<String>, options: Options) -> Self {
+        DetectState {
+            hint,
+            options,
+            ..DetectState::default()
+        }
+    }
+
+    /// Whether the text seen so far is enough to settle the charset.
+    ///
+    /// Detection is conclusive once the most likely candidate comes from a byte order mark, a
+    /// complete declaration, or the format of the text. Until then more of the text may turn up a
+    /// declaration, or the rest of one. It's also conclusive once the whole window has been seen,
+    /// as no more of the text is examined.
+    pub fn is_conclusive(&self) -> bool {
+        if self.bytes.len() >= self.limit() {
+            return true;
+        }
+        if self
+            .result
+            .warnings
+            .contains(&DetectionWarning::TruncatedDeclaration)
+        {
+            return false;
+        }
+
+        self.result.detections.first().is_some_and(|detection| {
+            matches!(
+                detection.source,
+                DetectionSource::ByteOrderMark
+                    | DetectionSource::Declaration
+                    | DetectionSource::Format
+            )
+        })
+    }
+
+    /// The result of detection on the text seen so far.
+    pub fn result(&self) -> &DetectionResult {
+        &self.result
+    }
+
+    /// The number of bytes of text seen so far.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns `true` if no text has been seen yet.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    // The most text that is examined, as per `detect`
+    fn limit(&self) -> usize {
+        let window_len = self.options.window_len();
+        self.options
+            .max_total_read
+            .map_or(window_len, |max_total_read| max_total_read.min(window_len))
+    }
+}
+
+/// Continue detection with the next bytes of the text.
+///
+/// This allows text from a stream that can't be rewound to be fed in piece by piece until
+/// detection is conclusive, such as when the bytes arrive in small network packets. Detection is
+/// repeated over all of the text seen so far, so a declaration split between calls is found. As
+/// with [`detect`](fn.detect.html), only the window set by the options is examined, so bytes
+/// beyond it are ignored, giving the same candidates as `detect` once the window is full. Once
+/// detection is conclusive, as per
+/// [`DetectState::is_conclusive`](struct.DetectState.html#method.is_conclusive), further bytes
+/// are ignored.
+///
+/// Returns the candidate charsets for the text seen so far, most likely first.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::DetectState;
+///
+/// let mut state = DetectState::new(None);
+/// xhtmlchardet::detect_more(&mut state, b"<?xml version=\"1.0\" enco");
+/// assert!(!state.is_conclusive());
+///
+/// let detected_charsets = xhtmlchardet::detect_more(&mut state, b"ding=\"ISO-8859-1\"?>");
+/// assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
+/// assert!(state.is_conclusive());
+/// ```
+pub fn detect_more(state: &mut DetectState, more_bytes: &[u8]) -> Vec<String> {
+    if !state.is_conclusive() {
+        let len = more_bytes.len().min(state.limit() - state.bytes.len());
+        state.bytes.extend_from_slice(&more_bytes[..len]);
+        state.result = detect_core(&state.bytes, state.hint.as_deref(), &state.options);
+    }
+
+    state.result.charsets()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use {detect_slice, PREFIX_LEN};
+
+    #[test]
+    fn test_declaration_split_across_calls() {
+        let mut state = DetectState::new(None);
+        assert_eq!(
+            detect_more(&mut state, b"<?xml version=\"1.0\" encoding=\"ISO-"),
+            vec!["iso-".to_string()]
+        );
+        assert!(!state.is_conclusive());
+
+        assert_eq!(
+            detect_more(&mut state, b"8859-1\"?><channel></channel>"),
+            vec!["iso-8859-1".to_string()]
+        );
+        assert!(state.is_conclusive());
+        assert!(state.result().warnings.is_empty());
+
+        // Later bytes are ignored
+        detect_more(&mut state, b"<meta charset=\"shift_jis\">");
+        assert_eq!(state.result().charsets(), vec!["iso-8859-1".to_string()]);
+    }
+
+    #[test]
+    fn test_declaration_past_window() {
+        let mut text = b"<html><head>".to_vec();
+        text.resize(PREFIX_LEN - 10, b' ');
+        let meta = b"<meta charset=\"windows-1252\">";
+
+        // Only the window is examined, as by detect
+        let mut state = DetectState::new(None);
+        assert_eq!(detect_more(&mut state, &text), vec!["utf-8".to_string()]);
+        assert!(!state.is_conclusive());
+        assert_eq!(detect_more(&mut state, meta), vec!["utf-8".to_string()]);
+        assert_eq!(state.len(), PREFIX_LEN);
+        assert!(state.is_conclusive());
+        for _ in 0..3 {
+            detect_more(&mut state, &text);
+        }
+        assert_eq!(state.len(), PREFIX_LEN);
+        let mut whole = text.clone();
+        whole.extend_from_slice(meta);
+        assert_eq!(state.result().charsets(), detect_slice(&whole, None));
+
+        // A larger window takes in the declaration
+        let options = Options {
+            scan_limit: 1024,
+            ..Options::default()
+        };
+        let mut state = DetectState::with_options(None, options);
+        detect_more(&mut state, &text);
+        assert_eq!(
+            detect_more(&mut state, meta),
+            vec!["windows-1252".to_string()]
+        );
+        assert_eq!(state.len(), text.len() + meta.len());
+    }
+
+    #[test]
+    fn test_max_total_read() {
+        let options = Options {
+            max_total_read: Some(8),
+            ..Options::default()
+        };
+        let mut state = DetectState::with_options(None, options);
+        detect_more(&mut state, b"<p>Hello</p>");
+        assert_eq!(state.len(), 8);
+        assert!(state.is_conclusive());
+    }
+}
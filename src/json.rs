@@ -0,0 +1,94 @@
+//! Formatting detection results as JSON for tools that print them.
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use DetectionResult;
+
+/// Format the candidates of a detection result as a JSON array.
+///
+/// Each candidate is an object holding its charset, the source it was derived from and the
+/// confidence that source warrants, as per
+/// [`DetectionSource::confidence`](enum.DetectionSource.html#method.confidence).
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// let mut text_cursor = Cursor::new(b"\xEF\xBB\xBF<?xml version=\"1.0\"?>".to_vec());
+/// let result = xhtmlchardet::detect_detailed(&mut text_cursor, None).unwrap();
+/// assert_eq!(
+///     xhtmlchardet::to_json(&result),
+///     r#"[{"charset":"utf-8","source":"bom","confidence":100}]"#
+/// );
+/// ```
+pub fn to_json(result: &DetectionResult) -> String {
+    let mut json = String::from("[");
+    for (index, detection) in result.detections.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        json.push_str("{\"charset\":");
+        push_string(&mut json, &detection.charset);
+        json.push_str(",\"source\":");
+        push_string(&mut json, detection.source.name());
+        // Writing to a String can't fail
+        let _ = write!(json, ",\"confidence\":{}}}", detection.source.confidence());
+    }
+    json.push(']');
+    json
+}
+
+// Append `value` as a quoted JSON string
+fn push_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use detect_core;
+    use Options;
+
+    #[test]
+    fn test_to_json() {
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>Hello</p>";
+        let result = detect_core(text, Some("utf-8"), &Options::default());
+        assert_eq!(
+            to_json(&result),
+            "[{\"charset\":\"iso-8859-1\",\"source\":\"declaration\",\"confidence\":80},\
+             {\"charset\":\"utf-8\",\"source\":\"hint\",\"confidence\":40}]"
+        );
+    }
+
+    #[test]
+    fn test_to_json_empty() {
+        assert_eq!(to_json(&DetectionResult::default()), "[]");
+    }
+
+    #[test]
+    fn test_to_json_escapes() {
+        let text = b"<meta charset=\"a\\b\x01\">";
+        let result = detect_core(text, None, &Options::default());
+        assert_eq!(
+            to_json(&result),
+            "[{\"charset\":\"a\\\\b\\u0001\",\"source\":\"declaration\",\"confidence\":80}]"
+        );
+    }
+}
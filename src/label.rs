@@ -0,0 +1,377 @@
+//! Canonicalising charset names without any text to detect.
+
+use alloc::string::{String, ToString};
+
+use normalise;
+
+// Canonical charset names, as returned by detection, each followed by its aliases. Every label of
+// the WHATWG Encoding Standard's label table is here. So are charsets the standard doesn't have,
+// like `utf-7`, `ucs-4le` and `ebcdic`, and other common aliases, like `latin10`. Charsets the
+// standard folds into a superset, such as `iso-8859-1` into `windows-1252`, keep their own name
+// here as the text may be read by something other than a browser.
+const LABELS: &[(&str, &[&str])] = &[
+    (
+        "utf-8",
+        &[
+            "utf8",
+            "unicode-1-1-utf-8",
+            "unicode11utf8",
+            "unicode20utf8",
+            "x-unicode20utf8",
+        ],
+    ),
+    (
+        "utf-16",
+        &["unicode", "ucs-2", "iso-10646-ucs-2", "csunicode"],
+    ),
+    ("utf-16le", &["unicodefeff"]),
+    ("utf-16be", &["unicodefffe"]),
+    ("utf-32", &["ucs-4", "iso-10646-ucs-4"]),
+    ("utf-32le", &[]),
+    ("utf-32be", &[]),
+    ("ucs-4le", &[]),
+    ("ucs-4be", &[]),
+    ("utf-7", &["unicode-1-1-utf-7", "csunicode11utf7"]),
+    (
+        "ascii",
+        &[
+            "us-ascii",
+            "ansi_x3.4-1968",
+            "iso-ir-6",
+            "iso646-us",
+            "iso_646.irv:1991",
+            "us",
+            "ibm367",
+            "cp367",
+            "csascii",
+        ],
+    ),
+    (
+        "iso-8859-1",
+        &[
+            "iso8859-1",
+            "iso88591",
+            "iso_8859-1",
+            "iso_8859-1:1987",
+            "iso-ir-100",
+            "latin1",
+            "l1",
+            "ibm819",
+            "cp819",
+            "csisolatin1",
+        ],
+    ),
+    (
+        "iso-8859-2",
+        &[
+            "iso8859-2",
+            "iso88592",
+            "iso_8859-2",
+            "iso_8859-2:1987",
+            "iso-ir-101",
+            "latin2",
+            "l2",
+            "csisolatin2",
+        ],
+    ),
+    (
+        "iso-8859-3",
+        &[
+            "iso8859-3",
+            "iso88593",
+            "iso_8859-3",
+            "iso_8859-3:1988",
+            "iso-ir-109",
+            "latin3",
+            "l3",
+            "csisolatin3",
+        ],
+    ),
+    (
+        "iso-8859-4",
+        &[
+            "iso8859-4",
+            "iso88594",
+            "iso_8859-4",
+            "iso_8859-4:1988",
+            "iso-ir-110",
+            "latin4",
+            "l4",
+            "csisolatin4",
+        ],
+    ),
+    (
+        "iso-8859-5",
+        &[
+            "iso8859-5",
+            "iso88595",
+            "iso_8859-5",
+            "iso_8859-5:1988",
+            "iso-ir-144",
+            "cyrillic",
+            "csisolatincyrillic",
+        ],
+    ),
+    (
+        "iso-8859-6",
+        &[
+            "iso8859-6",
+            "iso88596",
+            "iso_8859-6",
+            "iso_8859-6:1987",
+            "iso-8859-6-e",
+            "iso-8859-6-i",
+            "iso-ir-127",
+            "arabic",
+            "asmo-708",
+            "ecma-114",
+            "csiso88596e",
+            "csiso88596i",
+            "csisolatinarabic",
+        ],
+    ),
+    (
+        "iso-8859-7",
+        &[
+            "iso8859-7",
+            "iso88597",
+            "iso_8859-7",
+            "iso_8859-7:1987",
+            "iso-ir-126",
+            "greek",
+            "greek8",
+            "ecma-118",
+            "elot_928",
+            "sun_eu_greek",
+            "csisolatingreek",
+        ],
+    ),
+    (
+        "iso-8859-8",
+        &[
+            "iso8859-8",
+            "iso88598",
+            "iso_8859-8",
+            "iso_8859-8:1988",
+            "iso-8859-8-e",
+            "iso-ir-138",
+            "hebrew",
+            "visual",
+            "csiso88598e",
+            "csisolatinhebrew",
+        ],
+    ),
+    ("iso-8859-8-i", &["logical", "csiso88598i"]),
+    (
+        "iso-8859-9",
+        &[
+            "iso8859-9",
+            "iso88599",
+            "iso_8859-9",
+            "iso_8859-9:1989",
+            "iso-ir-148",
+            "latin5",
+            "l5",
+            "csisolatin5",
+        ],
+    ),
+    (
+        "iso-8859-10",
+        &[
+            "iso8859-10",
+            "iso885910",
+            "iso-ir-157",
+            "latin6",
+            "l6",
+            "csisolatin6",
+        ],
+    ),
+    ("iso-8859-13", &["iso8859-13", "iso885913"]),
+    ("iso-8859-14", &["iso8859-14", "iso885914", "latin8", "l8"]),
+    (
+        "iso-8859-15",
+        &[
+            "iso8859-15",
+            "iso885915",
+            "iso_8859-15",
+            "latin-9",
+            "latin9",
+            "l9",
+            "csisolatin9",
+        ],
+    ),
+    ("iso-8859-16", &["iso8859-16", "latin10", "l10"]),
+    ("windows-1250", &["cp1250", "x-cp1250"]),
+    ("windows-1251", &["cp1251", "x-cp1251"]),
+    ("windows-1252", &["cp1252", "x-cp1252"]),
+    ("windows-1253", &["cp1253", "x-cp1253"]),
+    ("windows-1254", &["cp1254", "x-cp1254"]),
+    ("windows-1255", &["cp1255", "x-cp1255"]),
+    ("windows-1256", &["cp1256", "x-cp1256"]),
+    ("windows-1257", &["cp1257", "x-cp1257"]),
+    ("windows-1258", &["cp1258", "x-cp1258"]),
+    (
+        "windows-874",
+        &[
+            "cp874",
+            "dos-874",
+            "tis-620",
+            "iso-8859-11",
+            "iso8859-11",
+            "iso885911",
+        ],
+    ),
+    ("koi8-r", &["koi8_r", "koi8", "koi", "cskoi8r"]),
+    ("koi8-u", &["koi8-ru"]),
+    ("ibm866", &["cp866", "866", "csibm866"]),
+    ("macintosh", &["mac", "x-mac-roman", "csmacintosh"]),
+    ("x-mac-cyrillic", &["x-mac-ukrainian"]),
+    (
+        "shift_jis",
+        &[
+            "shift-jis",
+            "sjis",
+            "x-sjis",
+            "ms_kanji",
+            "csshiftjis",
+            "windows-31j",
+            "ms932",
+        ],
+    ),
+    ("euc-jp", &["x-euc-jp", "cseucpkdfmtjapanese"]),
+    ("iso-2022-jp", &["csiso2022jp"]),
+    (
+        "euc-kr",
+        &[
+            "ks_c_5601-1987",
+            "ks_c_5601-1989",
+            "ksc5601",
+            "ksc_5601",
+            "korean",
+            "iso-ir-149",
+            "windows-949",
+            "cseuckr",
+            "csksc56011987",
+        ],
+    ),
+    // GB2312 and GBK are subsets of GB18030, which the Encoding Standard decodes them as
+    (
+        "gb18030",
+        &[
+            "gbk",
+            "gb2312",
+            "gb_2312",
+            "gb_2312-80",
+            "iso-ir-58",
+            "chinese",
+            "csgb2312",
+            "csiso58gb231280",
+            "x-gbk",
+            "cp936",
+            "windows-936",
+        ],
+    ),
+    ("big5", &["big5-hkscs", "cn-big5", "x-x-big5", "csbig5"]),
+    // Charsets that are unsafe to decode, as their escape sequences can hide markup
+    (
+        "replacement",
+        &[
+            "csiso2022kr",
+            "hz-gb-2312",
+            "iso-2022-cn",
+            "iso-2022-cn-ext",
+            "iso-2022-kr",
+        ],
+    ),
+    ("x-user-defined", &[]),
+    ("ebcdic", &[]),
+];
+
+// The canonical name of a lowercase charset label, as used by `normalise`
+pub(crate) fn canonical_name(label: &str) -> Option<&'static str> {
+    LABELS
+        .iter()
+        .find(|&&(canonical, aliases)| canonical == label || aliases.contains(&label))
+        .map(|&(canonical, _)| canonical)
+}
+
+/// Canonicalise a charset name.
+///
+/// The name is normalised as per [`detect`](fn.detect.html) and resolved to the canonical name
+/// of the charset, so that aliases like `latin1` and `ISO_8859-1:1987` resolve to `iso-8859-1`.
+/// `None` is returned if the name isn't a known charset.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// assert_eq!(xhtmlchardet::resolve_label("Latin1"), Some("iso-8859-1".to_string()));
+/// assert_eq!(xhtmlchardet::resolve_label("not-a-charset"), None);
+/// ```
+pub fn resolve_label(label: &str) -> Option<String> {
+    let name = normalise(label.trim());
+    LABELS
+        .iter()
+        .find(|&&(canonical, _)| canonical == name)
+        .map(|&(canonical, _)| canonical.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_canonical_names() {
+        for &(canonical, _) in LABELS {
+            assert_eq!(resolve_label(canonical), Some(canonical.to_string()));
+        }
+        assert_eq!(resolve_label(" UTF-8 "), Some("utf-8".to_string()));
+    }
+
+    #[test]
+    fn test_aliases() {
+        assert_eq!(resolve_label("UTF8"), Some("utf-8".to_string()));
+        assert_eq!(resolve_label("US-ASCII"), Some("ascii".to_string()));
+        assert_eq!(resolve_label("latin1"), Some("iso-8859-1".to_string()));
+        assert_eq!(resolve_label("cp1252"), Some("windows-1252".to_string()));
+        assert_eq!(resolve_label("cp:1251"), Some("windows-1251".to_string()));
+        assert_eq!(resolve_label("Shift-JIS"), Some("shift_jis".to_string()));
+        assert_eq!(resolve_label("GB2312"), Some("gb18030".to_string()));
+        assert_eq!(resolve_label("x-gbk"), Some("gb18030".to_string()));
+        for label in &[
+            "csiso2022kr",
+            "HZ-GB-2312",
+            "iso-2022-cn",
+            "iso-2022-cn-ext",
+            "ISO-2022-KR",
+        ] {
+            assert_eq!(resolve_label(label), Some("replacement".to_string()));
+        }
+        assert_eq!(
+            resolve_label("X-User-Defined"),
+            Some("x-user-defined".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unknown_labels() {
+        assert_eq!(resolve_label(""), None);
+        assert_eq!(resolve_label("not-a-charset"), None);
+        assert_eq!(resolve_label("utf-8; q=0.5"), None);
+    }
+
+    #[test]
+    fn test_aliases_are_unique() {
+        let mut seen = Vec::new();
+        for &(canonical, aliases) in LABELS {
+            for &name in Some(canonical).iter().chain(aliases) {
+                assert_eq!(name, name.to_lowercase(), "{} isn't lowercase", name);
+                assert_eq!(normalise(name), canonical);
+                assert!(!seen.contains(&name), "{} appears twice", name);
+                seen.push(name);
+            }
+        }
+    }
+}
@@ -11,18 +11,126 @@
 //! let detected_charsets: Vec<String> = xhtmlchardet::detect(&mut text_cursor, None).unwrap();
 //! assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
 //! ```
+//!
+//! ## Features
+//!
+//! * `brotli`: adds `detect_brotli` for detecting the charset of Brotli compressed streams.
+//! * `encoding_rs`: adds functions for bridging detected charsets with the [encoding_rs] crate.
+//! * `lru`: adds `CachingDetector`, which caches detection results for text that is seen
+//!   repeatedly.
+//! * `rayon`: makes `detect_paths` detect files in parallel.
+//! * `serde`: implements `Serialize` and `Deserialize` for `DetectionResult` and the types it
+//!   holds, along with `Confidence` and `BomKind`, so that results can be stored or sent on.
+//! * `std` (default): adds detection from `Read` streams and files. Without it the crate is
+//!   `no_std` and only needs `alloc`, with detection on byte slices by `detect_slice`,
+//!   `detect_more` and `DetectorChain::detect_slice`. The other features, except `serde`, all
+//!   require `std`.
+//! * `tokio`: adds `detect_async` for detecting the charset of Tokio `AsyncRead` streams.
+//! * `zstd`: adds `detect_zstd` for detecting the charset of Zstandard compressed streams.
+//!
+//! [encoding_rs]: https://crates.io/crates/encoding_rs
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+#[cfg(feature = "brotli")]
+extern crate brotli;
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "encoding_rs")]
+extern crate encoding_rs;
+#[cfg(feature = "lru")]
+extern crate lru;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+
+#[cfg(feature = "std")]
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::cmp::Ordering;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead, Read};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "lru")]
+mod cache;
+mod chain;
+mod charset;
+#[cfg(any(feature = "brotli", feature = "zstd"))]
+mod compression;
+mod detector;
+#[cfg(feature = "encoding_rs")]
+mod encoding_rs_ext;
+#[cfg(feature = "std")]
+mod error;
+mod framing;
+#[cfg(feature = "std")]
+mod fs;
+mod incremental;
+mod json;
+mod label;
+#[cfg(feature = "tokio")]
+mod tokio_ext;
+mod transfer_encoding;
+
+#[cfg(feature = "lru")]
+pub use cache::CachingDetector;
+pub use chain::{
+    ByteOrderMarkStage, DetectContext, DetectorChain, HintStage, MetaCharsetStage, Stage,
+    Utf8FallbackStage, Utf8MultibyteStage, XmlDeclarationStage,
+};
+#[cfg(feature = "std")]
+pub use charset::detect_typed;
+pub use charset::{DetectedCharset, Encoding};
+#[cfg(feature = "brotli")]
+pub use compression::detect_brotli;
+#[cfg(feature = "zstd")]
+pub use compression::detect_zstd;
+pub use detector::{Detector, DetectorBuilder};
+#[cfg(feature = "encoding_rs")]
+pub use encoding_rs_ext::{
+    candidate_encodings, decode, detect_encoding_rs, detect_with_encoding_rs_label,
+    is_decodable_as, transcode_to_utf8, Utf8TranscodingReader,
+};
+#[cfg(feature = "std")]
+pub use error::{try_detect, DetectError};
+pub use framing::{detect_field, Framing, LengthWidth};
+#[cfg(feature = "std")]
+pub use fs::{detect_from_path, detect_paths};
+pub use incremental::{detect_more, DetectState};
+pub use json::to_json;
+pub use label::resolve_label;
+#[cfg(feature = "tokio")]
+pub use tokio_ext::{detect_async, DetectAsync};
+#[cfg(feature = "std")]
+pub use transfer_encoding::detect_base64;
+pub use transfer_encoding::{
+    charset_from_content_type, detect_form_urlencoded, detect_mail_message,
+};
 
-use std::io::{self, Read};
+use label::canonical_name;
 
 #[derive(Debug)]
 struct Bom(u8, u8, u8, u8);
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Flavour {
-    UCS,
-    UTF,
-    EBCDIC,
-    ASCII,
+    Ucs,
+    Utf,
+    Utf7,
+    Ebcdic,
+    Ascii,
     Unknown,
 }
 
@@ -37,51 +145,490 @@ enum ByteOrder {
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Width {
-    EightBit = 8,
-    SixteenBit = 16,
-    ThirtyTwoBit = 32,
+    Eight = 8,
+    Sixteen = 16,
+    ThirtyTwo = 32,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct Descriptor(Flavour, Width, ByteOrder);
 
 // 32-Bit Encodings
-const UCS_4_BE: Descriptor = Descriptor(Flavour::UCS, Width::ThirtyTwoBit, ByteOrder::BigEndian);
-const UCS_4_LE: Descriptor = Descriptor(Flavour::UCS, Width::ThirtyTwoBit, ByteOrder::LittleEndian);
-const UCS_4_2143: Descriptor =
-    Descriptor(Flavour::UCS, Width::ThirtyTwoBit, ByteOrder::Unusual2143);
-const UCS_4_3412: Descriptor =
-    Descriptor(Flavour::UCS, Width::ThirtyTwoBit, ByteOrder::Unusual3412);
+const UCS_4_BE: Descriptor = Descriptor(Flavour::Ucs, Width::ThirtyTwo, ByteOrder::BigEndian);
+const UCS_4_LE: Descriptor = Descriptor(Flavour::Ucs, Width::ThirtyTwo, ByteOrder::LittleEndian);
+const UCS_4_2143: Descriptor = Descriptor(Flavour::Ucs, Width::ThirtyTwo, ByteOrder::Unusual2143);
+const UCS_4_3412: Descriptor = Descriptor(Flavour::Ucs, Width::ThirtyTwo, ByteOrder::Unusual3412);
 
 // 16-Bit Encodings
-const UTF_16_BE: Descriptor = Descriptor(Flavour::UTF, Width::SixteenBit, ByteOrder::BigEndian);
-const UTF_16_LE: Descriptor = Descriptor(Flavour::UTF, Width::SixteenBit, ByteOrder::LittleEndian);
+const UTF_16_BE: Descriptor = Descriptor(Flavour::Utf, Width::Sixteen, ByteOrder::BigEndian);
+const UTF_16_LE: Descriptor = Descriptor(Flavour::Utf, Width::Sixteen, ByteOrder::LittleEndian);
 
-const UTF_8: Descriptor = Descriptor(Flavour::UTF, Width::EightBit, ByteOrder::NotApplicable);
-const EBCDIC: Descriptor = Descriptor(Flavour::EBCDIC, Width::EightBit, ByteOrder::NotApplicable);
+const UTF_8: Descriptor = Descriptor(Flavour::Utf, Width::Eight, ByteOrder::NotApplicable);
+const UTF_7: Descriptor = Descriptor(Flavour::Utf7, Width::Eight, ByteOrder::NotApplicable);
+const EBCDIC: Descriptor = Descriptor(Flavour::Ebcdic, Width::Eight, ByteOrder::NotApplicable);
 
 // ASCII compatible encodings
 const ASCII_32BIT_BE: Descriptor =
-    Descriptor(Flavour::Unknown, Width::ThirtyTwoBit, ByteOrder::BigEndian);
-const ASCII_32BIT_LE: Descriptor = Descriptor(
-    Flavour::Unknown,
-    Width::ThirtyTwoBit,
-    ByteOrder::LittleEndian,
-);
+    Descriptor(Flavour::Unknown, Width::ThirtyTwo, ByteOrder::BigEndian);
+const ASCII_32BIT_LE: Descriptor =
+    Descriptor(Flavour::Unknown, Width::ThirtyTwo, ByteOrder::LittleEndian);
 const ASCII_16BIT_BE: Descriptor =
-    Descriptor(Flavour::Unknown, Width::SixteenBit, ByteOrder::BigEndian);
+    Descriptor(Flavour::Unknown, Width::Sixteen, ByteOrder::BigEndian);
 const ASCII_16BIT_LE: Descriptor =
-    Descriptor(Flavour::Unknown, Width::SixteenBit, ByteOrder::LittleEndian);
-const ASCII_8BIT: Descriptor =
-    Descriptor(Flavour::ASCII, Width::EightBit, ByteOrder::NotApplicable);
+    Descriptor(Flavour::Unknown, Width::Sixteen, ByteOrder::LittleEndian);
+const ASCII_8BIT: Descriptor = Descriptor(Flavour::Ascii, Width::Eight, ByteOrder::NotApplicable);
 
-/// Attempt to detect the character set of the supplied byte stream.
+// The default number of bytes following the first four that are searched for a declaration
+const SCAN_LIMIT: usize = 512;
+
+// The four bytes examined for a byte order mark plus the window searched for a declaration
+const PREFIX_LEN: usize = 4 + SCAN_LIMIT;
+// The number of bytes HTML parsers prescan for a meta charset
+const HTML_PRESCAN_LEN: usize = 1024;
+
+// How far into the text to look for a BOM preceded by junk in lenient mode
+const LEADING_JUNK_LIMIT: usize = 4;
+
+/// Where a detected charset was derived from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum DetectionSource {
+    /// An `encoding=` or `charset=` declaration in the text.
+    Declaration,
+    /// A `utf-8` declaration in text that isn't valid UTF-8, so the declaration is likely wrong.
+    /// Only checked in strict mode.
+    ContradictedDeclaration,
+    /// The `hint` supplied by the caller.
+    Hint,
+    /// The byte order mark or the arrangement of the first four bytes.
+    #[cfg_attr(feature = "serde", serde(rename = "bom"))]
+    ByteOrderMark,
+    /// A non-standard `<?charset name?>` processing instruction, only recognised in lenient mode.
+    ProcessingInstruction,
+    /// Sequences characteristic of UTF-8 that was decoded as a single byte charset and then
+    /// encoded as UTF-8 again, only recognised with heuristics in lenient mode.
+    Mojibake,
+    /// The language of the text, as given by a `lang` or `xml:lang` attribute, when the text is
+    /// neither declared nor valid UTF-8. Only used with heuristics.
+    Language,
+    /// The text is made up of well formed multi-byte sequences of GB18030, Shift_JIS or EUC-JP,
+    /// when it is neither declared nor valid UTF-8. Only used with heuristics.
+    ByteRanges,
+    /// The charset required by the `ContentKind` of the text.
+    Format,
+    /// The text was valid UTF-8 containing multi-byte sequences, which outweighs a declaration
+    /// or hint naming a single byte charset such as `iso-8859-1`.
+    Utf8Multibyte,
+    /// The text was valid UTF-8 and no other source applied.
+    Utf8Fallback,
+    /// There was no text at all, which is trivially valid UTF-8.
+    EmptyInput,
+}
+
+impl DetectionSource {
+    /// How much confidence a charset from this source warrants, from 0 to 100.
+    ///
+    /// A byte order mark, or the charset required by the format, is near certain. A declaration
+    /// in the text is usually right but is sometimes stale. A hint or processing instruction is
+    /// less reliable again, and text that merely happens to be valid UTF-8 warrants little
+    /// confidence.
+    pub fn confidence(&self) -> u8 {
+        match *self {
+            DetectionSource::ByteOrderMark | DetectionSource::Format => 100,
+            DetectionSource::Utf8Multibyte => 90,
+            DetectionSource::Declaration => 80,
+            DetectionSource::Hint => 40,
+            DetectionSource::ProcessingInstruction | DetectionSource::Mojibake => 30,
+            DetectionSource::ContradictedDeclaration | DetectionSource::Utf8Fallback => 20,
+            DetectionSource::Language
+            | DetectionSource::ByteRanges
+            | DetectionSource::EmptyInput => 10,
+        }
+    }
+
+    // A short name for the source, as used by `to_json` and the `Debug` of `Detection`
+    pub(crate) fn name(&self) -> &'static str {
+        match *self {
+            DetectionSource::Declaration => "declaration",
+            DetectionSource::ContradictedDeclaration => "contradicted-declaration",
+            DetectionSource::Hint => "hint",
+            DetectionSource::ByteOrderMark => "bom",
+            DetectionSource::ProcessingInstruction => "processing-instruction",
+            DetectionSource::Mojibake => "mojibake",
+            DetectionSource::Language => "language",
+            DetectionSource::ByteRanges => "byte-ranges",
+            DetectionSource::Format => "format",
+            DetectionSource::Utf8Multibyte => "utf8-multibyte",
+            DetectionSource::Utf8Fallback => "utf8-fallback",
+            DetectionSource::EmptyInput => "empty-input",
+        }
+    }
+}
+
+/// How reliable a detected charset is, as returned by
+/// [`detect_with_confidence`](fn.detect_with_confidence.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Confidence {
+    /// From a byte order mark, or required by the format of the text.
+    Certain,
+    /// From a declaration in the text.
+    Declared,
+    /// From the `hint` supplied by the caller.
+    Hint,
+    /// Inferred from the content of the text, such as it being valid UTF-8.
+    Guessed,
+}
+
+impl From<DetectionSource> for Confidence {
+    fn from(source: DetectionSource) -> Self {
+        match source {
+            DetectionSource::ByteOrderMark | DetectionSource::Format => Confidence::Certain,
+            DetectionSource::Declaration | DetectionSource::ProcessingInstruction => {
+                Confidence::Declared
+            }
+            DetectionSource::Hint => Confidence::Hint,
+            DetectionSource::ContradictedDeclaration
+            | DetectionSource::Mojibake
+            | DetectionSource::Language
+            | DetectionSource::ByteRanges
+            | DetectionSource::Utf8Multibyte
+            | DetectionSource::Utf8Fallback
+            | DetectionSource::EmptyInput => Confidence::Guessed,
+        }
+    }
+}
+
+/// A single detected charset and where it came from.
 ///
-/// `reader` is expected to be positioned at the start of the stream. `detect` will read up to 512
-/// bytes in order to determine the encoding.
+/// The `Debug` representation is compact and stable, for use in snapshot tests, such as
+/// `"utf-8" from bom (confidence 100)`.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Detection {
+    /// The normalised name of the charset.
+    pub charset: String,
+    /// Where the charset was derived from.
+    pub source: DetectionSource,
+}
+
+impl fmt::Debug for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} from {} (confidence {})",
+            self.charset,
+            self.source.name(),
+            self.source.confidence()
+        )
+    }
+}
+
+/// Something noteworthy about the text found during detection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum DetectionWarning {
+    /// The text contains a `charset` or `encoding` declaration obfuscated with character
+    /// references, such as `&#x63;harset=`, which may be an attempt to spoof the charset. Only
+    /// checked in strict mode.
+    SuspiciousDeclaration,
+    /// The value of the declaration has no matching closing quote before the end of the tag or
+    /// the end of the text examined, as can happen with truncated text. The charset was taken from what
+    /// precedes the end, so it may be incomplete.
+    TruncatedDeclaration,
+    /// The text contains sequences such as `Ã©`, which is what `é` becomes when UTF-8 is decoded
+    /// as `windows-1252` and encoded as UTF-8 again. The text likely needs to be decoded twice to
+    /// be read correctly. Only checked with heuristics in lenient mode.
+    Mojibake,
+    /// The text declares `utf-8` but isn't valid UTF-8. The declared charset is kept as a
+    /// candidate with `DetectionSource::ContradictedDeclaration`, behind any others. Only checked
+    /// in strict mode.
+    ContradictedDeclaration,
+    /// The byte order mark is for a different encoding form from the declaration, such as a
+    /// UTF-16 byte order mark with `encoding="iso-8859-1"`, which the XML specification treats as
+    /// a fatal error. Only checked in strict mode, except for a UTF-8 byte order mark, which
+    /// is authoritative and leaves the declaration out of the candidates in any mode.
+    ConflictingByteOrderMark,
+}
+
+/// The result of detection, with the reason for each candidate charset.
 ///
-/// The optional `hint` is a possible encoding name for the text that may have been received
-/// externally to the text itself, such as from HTTP header.
+/// The `Debug` representation lists the detections, followed by the warnings and the declaration
+/// offset, and is stable for use in snapshot tests.
+#[derive(Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct DetectionResult {
+    /// The candidate charsets, most likely first.
+    pub detections: Vec<Detection>,
+    /// Anything noteworthy found during detection.
+    pub warnings: Vec<DetectionWarning>,
+    /// The offset in bytes from the start of the text of the `encoding=` or `charset=`
+    /// declaration in markup, if one was found.
+    pub declaration_offset: Option<usize>,
+}
+
+impl DetectionResult {
+    /// The candidate charset names, most likely first.
+    ///
+    /// This is the same as the value returned by [`detect`](fn.detect.html). Each charset
+    /// appears once, even when `Options::record_agreeing_bom` has it detected more than once.
+    /// The `utf-8` from `DetectionSource::EmptyInput` is left out, as text that isn't there
+    /// doesn't have a charset.
+    pub fn charsets(&self) -> Vec<String> {
+        let mut charsets: Vec<String> = Vec::with_capacity(self.detections.len());
+        for detection in &self.detections {
+            if detection.source != DetectionSource::EmptyInput
+                && !charsets.contains(&detection.charset)
+            {
+                charsets.push(detection.charset.clone());
+            }
+        }
+        charsets
+    }
+
+    /// The gap between the confidence of the two most confident candidates, as per
+    /// [`DetectionSource::confidence`](enum.DetectionSource.html#method.confidence).
+    ///
+    /// A small margin means sources disagree, such as a byte order mark conflicting with a
+    /// declaration, so the result is ambiguous. `None` is returned when there are fewer than two
+    /// candidates.
+    pub fn margin(&self) -> Option<u8> {
+        let mut confidences: Vec<u8> = self
+            .detections
+            .iter()
+            .map(|detection| detection.source.confidence())
+            .collect();
+        if confidences.len() < 2 {
+            return None;
+        }
+
+        confidences.sort_unstable_by(|a, b| b.cmp(a));
+        Some(confidences[0] - confidences[1])
+    }
+
+    fn push(&mut self, charset: String, source: DetectionSource) {
+        if !self
+            .detections
+            .iter()
+            .any(|detection| detection.charset == charset)
+        {
+            self.detections.push(Detection { charset, source });
+        }
+    }
+}
+
+impl fmt::Debug for DetectionResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DetectionResult")
+            .field("detections", &self.detections)
+            .field("warnings", &self.warnings)
+            .field("declaration_offset", &self.declaration_offset)
+            .finish()
+    }
+}
+
+/// What the first four bytes of a text reveal about how it's encoded, as returned by
+/// [`detect_structure`](fn.detect_structure.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StructuralInfo {
+    descriptor: Descriptor,
+}
+
+impl StructuralInfo {
+    /// The width in bits of the code units of the text: 8, 16 or 32.
+    pub fn width(&self) -> u8 {
+        let Descriptor(_, width, _) = self.descriptor;
+        width as u8
+    }
+
+    /// Returns `true` if the text starts with a byte order mark.
+    ///
+    /// This includes the UTF-7 signature, even though its length is 0 as per
+    /// [`bom_length`](fn.bom_length.html).
+    pub fn has_bom(&self) -> bool {
+        bom_length(self) > 0 || self.descriptor == UTF_7
+    }
+}
+
+/// A byte order mark, as returned by [`detect_bom`](fn.detect_bom.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum BomKind {
+    /// `EF BB BF`
+    Utf8,
+    /// `FF FE`, other than at the start of a UCS-4 byte order mark.
+    Utf16Le,
+    /// `FE FF`, other than at the start of a UCS-4 byte order mark.
+    Utf16Be,
+    /// `FF FE 00 00`
+    Ucs4Le,
+    /// `00 00 FE FF`
+    Ucs4Be,
+    /// `00 00 FF FE`, UCS-4 in the unusual 2143 byte order.
+    Ucs4Unusual2143,
+    /// `FE FF 00 00`, UCS-4 in the unusual 3412 byte order.
+    Ucs4Unusual3412,
+    /// The UTF-7 signature, `+/v` followed by one of `8`, `9`, `+` or `/`.
+    Utf7,
+}
+
+impl BomKind {
+    /// The length in bytes of the byte order mark, as per [`bom_length`](fn.bom_length.html).
+    ///
+    /// This is 0 for UTF-7, as the last character of its signature shares bits with the next
+    /// character.
+    pub fn length(&self) -> usize {
+        descriptor_bom_length(&self.descriptor())
+    }
+
+    /// The charset indicated by the byte order mark, as reported by [`detect`](fn.detect.html).
+    ///
+    /// UCS-4 in an unusual byte order has no charset.
+    pub fn charset(&self) -> Option<&'static str> {
+        bom_charset(Some(&self.descriptor()))
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        match *self {
+            BomKind::Utf8 => UTF_8,
+            BomKind::Utf16Le => UTF_16_LE,
+            BomKind::Utf16Be => UTF_16_BE,
+            BomKind::Ucs4Le => UCS_4_LE,
+            BomKind::Ucs4Be => UCS_4_BE,
+            BomKind::Ucs4Unusual2143 => UCS_4_2143,
+            BomKind::Ucs4Unusual3412 => UCS_4_3412,
+            BomKind::Utf7 => UTF_7,
+        }
+    }
+}
+
+/// The kind of text being detected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ContentKind {
+    /// XML or HTML, which may declare its charset.
+    #[default]
+    Markup,
+    /// An XML part extracted from an OpenDocument or Office Open XML container, such as
+    /// `content.xml`. These are always UTF-8, so any declarations in the text are ignored and the
+    /// text is only validated as UTF-8.
+    OfficeXml,
+    /// HTML, possibly a template with embedded server side code.
+    ///
+    /// This is detected as per `Markup`, except that at least the first 1024 bytes are examined,
+    /// as HTML parsers do when prescanning for a meta charset, and declarations after `</head>`
+    /// are ignored. When `Options::strict` is set, declarations inside `<?php ... ?>` blocks are
+    /// ignored as they are part of the code rather than the page.
+    Html,
+    /// A GNU gettext `.po` or `.pot` file. The charset is taken from the `Content-Type` in the
+    /// header entry, the `msgstr` of the initial empty `msgid`.
+    Po,
+    /// A vCard `.vcf` file. The charset is taken from the `CHARSET=` parameters of its
+    /// properties, as used by vCard 2.1, which are matched regardless of case.
+    VCard,
+    /// An Advanced SubStation Alpha `.ass` or `.ssa` subtitle file. The charset is taken from a
+    /// `Charset:` or `Encoding:` field in the `[Script Info]` section, if present.
+    Ass,
+    /// Delimited text such as a `.csv` or `.tsv` spreadsheet export. These have no way to declare
+    /// their charset, so only the byte order mark and UTF-8 validity are used and anything
+    /// resembling a declaration in a cell is ignored.
+    DelimitedText,
+    /// A Rich Text Format `.rtf` document. The charset is taken from the code page given by the
+    /// `\ansicpg` control word, such as `windows-1252` for `\ansicpg1252`. Code pages without
+    /// a well known name are returned as, for example, `cp437`.
+    Rtf,
+    /// A CSS stylesheet. The charset is taken from an `@charset "name";` rule, which must be the
+    /// very first thing in the text after any byte order mark, written exactly so with a single
+    /// space and double quotes. As per the CSS specification, a declared `utf-16be` or
+    /// `utf-16le` is taken to be `utf-8`, since the rule couldn't have been read otherwise.
+    Css,
+}
+
+/// Options that control detection.
+///
+/// The default options match the behaviour of [`detect`](fn.detect.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// Recognise non-standard charset declarations that appear in the wild.
+    ///
+    /// When set, a `<?charset name?>` processing instruction is used as a low priority source
+    /// of the charset. Values delimited with backticks, as in ``charset=`utf-8` ``, are
+    /// recognised. A few garbled leading bytes, such as a mangled byte order mark, don't
+    /// prevent a declaration that follows them being found.
+    pub lenient: bool,
+    /// Apply additional checks to guard against malformed or malicious text.
+    ///
+    /// When set, declarations obfuscated with character references are reported as
+    /// `DetectionWarning::SuspiciousDeclaration`. With `ContentKind::Html`, declarations inside
+    /// `<?php ... ?>` blocks are ignored. An `encoding=` outside of the XML declaration, such as
+    /// a bogus attribute on the root element, is ignored. A `utf-8` declaration in text that
+    /// isn't valid UTF-8 is reported as `DetectionWarning::ContradictedDeclaration`, and a
+    /// declaration at odds with the byte order mark as `DetectionWarning::ConflictingByteOrderMark`.
+    pub strict: bool,
+    /// The kind of text being detected.
+    pub content_kind: ContentKind,
+    /// The maximum number of bytes that will be read from the reader.
+    ///
+    /// Detection never reads more than it needs to fill the window it examines, so this only
+    /// needs to be set to impose a tighter limit. When `None` the limit is the size of the window
+    /// plus the four bytes examined for a byte order mark.
+    pub max_total_read: Option<usize>,
+    /// Leave the byte order of UTF-16 and UTF-32 for the consumer to resolve.
+    ///
+    /// When set, a bare `utf-16` declaration or hint is returned as is rather than having its
+    /// byte order resolved, and a UTF-16 or UCS-4 byte order mark is reported as `utf-16` or
+    /// `utf-32`. Charsets that name a byte order explicitly, such as `utf-16le`, are unaffected.
+    pub emit_bare_unicode_widths: bool,
+    /// Look for patterns in the content of the text that suggest a charset.
+    ///
+    /// Text that is neither declared nor valid UTF-8 has the legacy charsets typically used for
+    /// the language given by a `lang` or `xml:lang` attribute suggested as candidates, such as
+    /// `windows-1251` and `koi8-r` for `lang="ru"`. Failing that, text made up of well formed
+    /// multi-byte sequences has `shift_jis` or `euc-jp` suggested when they include kana, and
+    /// `gb18030` otherwise. Combined with `lenient`, text that appears to have been encoded as
+    /// UTF-8 twice has `utf-8` suggested as a candidate, along with `DetectionWarning::Mojibake`.
+    pub heuristics: bool,
+    /// Record the byte order mark as a source of its charset even when an earlier source, such
+    /// as a declaration, already produced the same charset.
+    ///
+    /// Normally each charset is detected once, from the first source that produced it, so a BOM
+    /// that agrees with the declaration isn't visible in `DetectionResult::detections`. When set
+    /// both are present, which is useful for auditing. The candidate charsets are unaffected.
+    pub record_agreeing_bom: bool,
+    /// The number of bytes following the first four that are searched for a declaration.
+    ///
+    /// The default of 512 suits most text. Increase it for text where the declaration may
+    /// follow a long leading comment or license block. With `ContentKind::Html` at least the
+    /// first 1024 bytes are always searched.
+    pub scan_limit: usize,
+    /// Rename each detected charset, after the built-in normalisation, to impose a naming policy
+    /// of your own, such as uppercase names.
+    ///
+    /// The charsets from every source are renamed, including the hint and the byte order mark.
+    /// Charsets that end up with the same name are only returned once by
+    /// [`detect`](fn.detect.html).
+    pub canonicalizer: Option<Canonicalizer>,
+    /// Report a UCS-4 byte order mark as `utf-32le` or `utf-32be` rather than `ucs-4le` or
+    /// `ucs-4be`.
+    ///
+    /// UTF-32 is UCS-4 restricted to the range of Unicode, so the two are interchangeable for
+    /// text. The UTF-32 names are the ones expected by most decoders.
+    pub prefer_utf32_labels: bool,
+    /// Report a declared or hinted `iso-8859-1` as `windows-1252` when the text contains bytes
+    /// from 0x80 to 0x9F.
+    ///
+    /// Those bytes are control codes in ISO-8859-1 but printable characters in Windows-1252, such
+    /// as curly quotes and dashes, so text containing them was almost certainly mislabelled. This
+    /// is how browsers decode such text, as per the HTML specification.
+    pub latin1_as_windows1252: bool,
+}
+
+/// A function that renames detected charsets, as per `Options::canonicalizer`.
+///
+/// Two canonicalizers are equal if one is a clone of the other.
 ///
 /// ### Example
 ///
@@ -89,213 +636,4064 @@ const ASCII_8BIT: Descriptor =
 /// use std::io::Cursor;
 /// extern crate xhtmlchardet;
 ///
-/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
-/// let mut text_cursor = Cursor::new(text.to_vec());
-/// let detected_charsets = xhtmlchardet::detect(&mut text_cursor, None);
-/// assert_eq!(detected_charsets.unwrap_or(vec![]), vec!["iso-8859-1".to_string()]);
+/// use xhtmlchardet::{Canonicalizer, Options};
+///
+/// let options = Options {
+///     canonicalizer: Some(Canonicalizer::new(|charset| charset.to_uppercase())),
+///     ..Options::default()
+/// };
+/// let mut text_cursor = Cursor::new(b"<?xml version=\"1.0\" encoding=\"iso-8859-1\"?>".to_vec());
+/// let detected_charsets = xhtmlchardet::detect_with_options(&mut text_cursor, None, &options);
+/// assert_eq!(detected_charsets.unwrap(), vec!["ISO-8859-1".to_string()]);
 /// ```
-pub fn detect<R: Read>(reader: &mut R, hint: Option<String>) -> Result<Vec<String>, io::Error> {
-    // Read the first 4 bytes and see if they help
-    let mut first_four_bytes = [0u8; 4];
-    reader.read_exact(&mut first_four_bytes)?;
-
-    let bom = Bom(
-        first_four_bytes[0],
-        first_four_bytes[1],
-        first_four_bytes[2],
-        first_four_bytes[3],
-    );
-
-    let possible_encoding = detect_byte_order_mark(&bom);
-
-    // Now that byte size may have been determined try reading the first 512ish bytes to read an
-    // encoding declaration
-    let mut buf = [0u8; 512];
-    loop {
-        match reader.read(&mut buf) {
-            Ok(0) => return Ok(Vec::new()), // eof
-            Ok(_n) => break,
-            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {} // retry
-            Err(err) => return Err(err),
-        };
+#[derive(Clone)]
+pub struct Canonicalizer(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl Canonicalizer {
+    /// Create a canonicalizer that renames charsets with `rename`.
+    pub fn new<F>(rename: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        Canonicalizer(Arc::new(rename))
     }
 
-    let mut candidates = Vec::with_capacity(3);
+    fn rename(&self, charset: &str) -> String {
+        (self.0)(charset)
+    }
+}
 
-    // Look for encoding="", charset="?"?
-    search("encoding=", &buf, possible_encoding.as_ref())
-        .or_else(|| search("charset=", &buf, possible_encoding.as_ref()))
-        .map(normalise)
-        .map(|encoding| {
-            push_if_not_contains(
-                &mut candidates,
-                endianify(&encoding, possible_encoding.as_ref()),
-            )
-        });
+impl fmt::Debug for Canonicalizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Canonicalizer")
+    }
+}
 
-    // Consider hint
-    hint.map(normalise).map(|encoding| {
-        push_if_not_contains(
-            &mut candidates,
-            endianify(&encoding, possible_encoding.as_ref()),
-        )
-    });
+impl PartialEq for Canonicalizer {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
 
-    // Include info from BOM detection
-    match possible_encoding {
-        Some(UCS_4_LE) => Some("ucs-4le"),
-        Some(UCS_4_BE) => Some("ucs-4be"),
-        Some(UTF_16_LE) => Some("utf-16le"),
-        Some(UTF_16_BE) => Some("utf-16be"),
-        Some(Descriptor(Flavour::UTF, Width::EightBit, _)) => Some("utf-8"),
-        Some(EBCDIC) => Some("ebcdic"),
-        _ => None,
+impl Eq for Canonicalizer {}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            lenient: false,
+            strict: false,
+            content_kind: ContentKind::default(),
+            max_total_read: None,
+            emit_bare_unicode_widths: false,
+            heuristics: false,
+            record_agreeing_bom: false,
+            scan_limit: SCAN_LIMIT,
+            canonicalizer: None,
+            prefer_utf32_labels: false,
+            latin1_as_windows1252: false,
+        }
     }
-    .map(|encoding| push_if_not_contains(&mut candidates, encoding.to_string()));
+}
 
-    // Otherwise test if UTF-8
-    if candidates.is_empty() && std::str::from_utf8(&buf).is_ok() {
-        candidates.push("utf-8".to_string());
+impl Options {
+    /// The number of bytes at the start of the text that are examined: the first four bytes
+    /// followed by up to `scan_limit` more, or at least 1024 with `ContentKind::Html`.
+    pub fn window_len(&self) -> usize {
+        let window_len = 4 + self.scan_limit;
+        if self.content_kind == ContentKind::Html {
+            window_len.max(HTML_PRESCAN_LEN)
+        } else {
+            window_len
+        }
     }
 
-    Ok(candidates)
+    #[cfg(feature = "std")]
+    fn read_limit(&self) -> u64 {
+        self.max_total_read.unwrap_or_else(|| self.window_len()) as u64
+    }
 }
 
-fn detect_byte_order_mark(bom: &Bom) -> Option<Descriptor> {
-    // Can do below without the Bom type if slice pattern syntax becomes non-experimental
-    // let possible = match first_four_bytes {
-    //     // With Byte Order Mark
-    //     [0x00, 0x00, 0xFE, 0xFF]             => Some("UCS-4BE"),
-    //     [0xFF, 0xFE, 0x00, 0x00]             => Some("UCS-4LE"),
-    //     [0x00, 0x00, 0xFF, 0xFE]             => Some("UCS-4OE"),
-    //     [0xFE, 0xFF, 0x00, 0x00]             => Some("UCS-4EO"),
-    //     [0xFE, 0xFF, c, d] if c > 0 && d > 0 => Some("UTF-16BE"),
-    //     [0xFF, 0xFE, c, d] if c > 0 && d > 0 => Some("UCS-16LE"),
-    //     [0xEF, 0xBB, 0xBF, _   ]             => Some("UTF-8"),
+/// Attempt to detect the character set of the supplied byte stream.
+///
+/// `reader` is expected to be positioned at the start of the stream. `detect` will read up to 512
+/// bytes in order to determine the encoding. An empty stream has no candidates, while a stream
+/// of one to three bytes is an error of kind `UnexpectedEof`. Text that is nothing but NUL bytes
+/// isn't taken to be UTF-8.
+///
+/// The optional `hint` is a possible encoding name for the text that may have been received
+/// externally to the text itself, such as from HTTP header. Use
+/// [`charset_from_content_type`](fn.charset_from_content_type.html) to take it from a
+/// `Content-Type` header. It may also be a list of names in the
+/// style of an `Accept-Charset` header, like `utf-8, iso-8859-1;q=0.5`, in which case each is a
+/// candidate in order of preference. When the most preferred name is an EBCDIC code page, such
+/// as `cp037`, the text isn't searched for a declaration or tested for UTF-8.
+///
+/// Charset names are lowercased and aliases resolved using the labels of the WHATWG Encoding
+/// Standard, so `latin1` and `ISO_8859-1:1987` are both reported as `iso-8859-1`. Unknown names
+/// pass through lowercased.
+///
+/// A UCS-4 byte order mark is reported as `ucs-4le` or `ucs-4be`. Use
+/// [`detect_with_options`](fn.detect_with_options.html) with `Options::prefer_utf32_labels` for
+/// the equivalent `utf-32le` or `utf-32be` expected by most decoders.
+///
+/// A UTF-8 byte order mark is authoritative, as per the HTML and XML specifications, so a
+/// declaration naming some other charset after it, such as one left behind when a file was
+/// re-saved as UTF-8, isn't a candidate.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let detected_charsets = xhtmlchardet::detect(&mut text_cursor, None);
+/// assert_eq!(detected_charsets.unwrap_or(vec![]), vec!["iso-8859-1".to_string()]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect<R: Read>(reader: &mut R, hint: Option<String>) -> Result<Vec<String>, io::Error> {
+    Detector::default().detect(reader, hint)
+}
 
-    //     //  Without Byte Order Mark
-    //     [0x00, 0x00, 0x00, 0x3C] |
-    //     [0x3C, 0x00, 0x00, 0x00] |
-    //     [0x00, 0x00, 0x3C, 0x00] |
-    //     [0x00, 0x3C, 0x00, 0x00]             => Some("32-bit"),
-    //     [0x00, 0x3C, 0x00, 0x3F]             => Some("16-bit Big Endian"),
-    //     [0x3C, 0x00, 0x3F, 0x00]             => Some("16-bit Little Endian"),
-    //     [0x3C, 0x3F, 0x78, 0x6D]             => Some("8-bit"),
-    //     [0x4C, 0x6F, 0xA7, 0x94]             => Some("EBCDIC"),
-    //     // This may be UTF-8 without an encoding declaration as this is not required
-    //     // for UTF-8
-    //     _                                    => Some("Other"),
-    // };
+/// Attempt to detect the character set of the supplied byte stream using the supplied options.
+///
+/// This is the same as [`detect`](fn.detect.html) with behaviour adjusted by `options`.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::Options;
+///
+/// let text = b"<?charset ISO-8859-1?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let options = Options { lenient: true, ..Options::default() };
+/// let detected_charsets = xhtmlchardet::detect_with_options(&mut text_cursor, None, &options);
+/// assert_eq!(detected_charsets.unwrap_or(vec![]), vec!["iso-8859-1".to_string()]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_with_options<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+    options: &Options,
+) -> Result<Vec<String>, io::Error> {
+    detect_stream(reader, hint.as_deref(), options)
+}
 
-    // http://www.w3.org/TR/2004/REC-xml-20040204/#sec-guessing-no-ext-info
-    match *bom {
-        // With Byte Order Mark
-        Bom(0x00, 0x00, 0xFE, 0xFF) => Some(UCS_4_BE),
-        Bom(0xFF, 0xFE, 0x00, 0x00) => Some(UCS_4_LE),
-        Bom(0x00, 0x00, 0xFF, 0xFE) => Some(UCS_4_2143),
-        Bom(0xFE, 0xFF, 0x00, 0x00) => Some(UCS_4_3412),
-        Bom(0xFE, 0xFF, c, d) if c > 0 || d > 0 => Some(UTF_16_BE),
-        Bom(0xFF, 0xFE, c, d) if c > 0 || d > 0 => Some(UTF_16_LE),
-        Bom(0xEF, 0xBB, 0xBF, _) => Some(UTF_8),
+/// Attempt to detect the character set of the supplied byte stream with a borrowed hint.
+///
+/// This is the same as [`detect`](fn.detect.html), but saves allocating a `String` for a hint
+/// that is already at hand as a `&str`, such as a slice of a parsed header.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// let mut text_cursor = Cursor::new(b"<p>caf\xE9</p>".to_vec());
+/// let content_type = "text/html; charset=ISO-8859-1";
+/// let hint = content_type.split("charset=").nth(1);
+/// let detected_charsets = xhtmlchardet::detect_with_str_hint(&mut text_cursor, hint);
+/// assert_eq!(detected_charsets.unwrap_or(vec![]), vec!["iso-8859-1".to_string()]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_with_str_hint<R: Read>(
+    reader: &mut R,
+    hint: Option<&str>,
+) -> Result<Vec<String>, io::Error> {
+    detect_stream(reader, hint, &Options::default())
+}
 
-        //  Without Byte Order Mark
-        Bom(0x00, 0x00, 0x00, 0x3C) => Some(ASCII_32BIT_BE),
-        Bom(0x3C, 0x00, 0x00, 0x00) => Some(ASCII_32BIT_LE),
-        Bom(0x00, 0x00, 0x3C, 0x00) => Some(Descriptor(
-            Flavour::Unknown,
-            Width::ThirtyTwoBit,
-            ByteOrder::Unusual2143,
-        )),
-        Bom(0x00, 0x3C, 0x00, 0x00) => Some(Descriptor(
-            Flavour::Unknown,
-            Width::ThirtyTwoBit,
-            ByteOrder::Unusual3412,
-        )),
-        Bom(0x00, 0x3C, 0x00, 0x3F) => Some(ASCII_16BIT_BE),
-        Bom(0x3C, 0x00, 0x3F, 0x00) => Some(ASCII_16BIT_LE),
-        Bom(0x3C, 0x3F, 0x78, 0x6D) => Some(ASCII_8BIT),
-        Bom(0x4C, 0x6F, 0xA7, 0x94) => Some(EBCDIC),
-        // This may be UTF-8 without an encoding declaration as this is not required
-        // for UTF-8
-        _ => None,
-    }
+// Read the window of `reader` examined by `detect` and detect its charset
+#[cfg(feature = "std")]
+fn detect_stream<R: Read>(
+    reader: &mut R,
+    hint: Option<&str>,
+    options: &Options,
+) -> Result<Vec<String>, io::Error> {
+    let bytes = read_stream_window(reader, options)?;
+    charsets_for_window(&bytes, hint, options)
 }
 
-fn normalise<S: AsRef<str>>(encoding: S) -> String {
-    encoding
-        .as_ref()
-        .to_lowercase()
-        .replace("us-ascii", "ascii")
-        .replace("utf8", "utf-8")
-        .replace("shift-jis", "shift_jis")
+// Read the window of `reader` examined by `detect`
+#[cfg(feature = "std")]
+fn read_stream_window<R: Read>(reader: &mut R, options: &Options) -> Result<Vec<u8>, io::Error> {
+    // Keep reading as streams such as sockets and pipes return short reads
+    let mut buf = vec![0u8; options.window_len()];
+    let len = read_prefix(&mut reader.take(options.read_limit()), &mut buf)?;
+    buf.truncate(len);
+    Ok(buf)
 }
 
-fn push_if_not_contains<T: PartialEq>(vec: &mut Vec<T>, item: T) {
-    if !vec.contains(&item) {
-        vec.push(item);
+// Detect the charset of the window read from a stream by `detect`, returning `None` if there's
+// nothing to detect. An empty stream has no charset, otherwise the first four bytes are required
+// and are only enough to go on by themselves when they start with a byte order mark.
+#[cfg(feature = "std")]
+fn detect_window(
+    bytes: &[u8],
+    hint: Option<&str>,
+    options: &Options,
+) -> Result<Option<DetectionResult>, io::Error> {
+    match bytes.len() {
+        0 => Ok(None),
+        1..=3 => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "failed to fill whole buffer",
+        )),
+        4 if detect_structure(bytes).map_or(0, |info| bom_length(&info)) == 0 => Ok(None),
+        _ => Ok(Some(detect_core(bytes, hint, options))),
     }
 }
 
-fn endianify(encoding: &str, descriptor: Option<&Descriptor>) -> String {
-    let ascii = ASCII_8BIT;
-    let &Descriptor(_, _, ref order) = descriptor.unwrap_or(&ascii);
+// The candidate charsets of the window read from a stream, as per `detect_window`
+#[cfg(feature = "std")]
+fn charsets_for_window(
+    bytes: &[u8],
+    hint: Option<&str>,
+    options: &Options,
+) -> Result<Vec<String>, io::Error> {
+    Ok(detect_window(bytes, hint, options)?.map_or_else(Vec::new, |result| result.charsets()))
+}
 
-    match encoding {
-        "utf-16" => match *order {
-            ByteOrder::LittleEndian => "utf-16le".to_string(),
-            ByteOrder::BigEndian => "utf-16be".to_string(),
-            _ => encoding.to_string(),
-        },
-        _ => encoding.to_string(),
-    }
+/// Attempt to detect the character set of the supplied buffered byte stream without consuming
+/// any of it.
+///
+/// The bytes are inspected in place with `BufRead::fill_buf`, so `reader` is left positioned
+/// at the start of the text, ready to be read in full by a decoder or parser. This is the way to
+/// detect and then decode a stream that can't be rewound in a single pass. Wrap a plain reader in
+/// a `std::io::BufReader` to satisfy the `BufRead` bound.
+///
+/// Only the bytes that `fill_buf` makes available are examined, up to the size of the window
+/// examined by [`detect`](fn.detect.html), as per
+/// [`Options::window_len`](struct.Options.html#method.window_len). A `BufReader` fills its
+/// buffer with a single read, which may return less than that from a pipe or socket. Unlike `detect`, text shorter than four bytes
+/// isn't an error.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::{BufReader, Cursor, Read};
+/// extern crate xhtmlchardet;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let mut reader = BufReader::new(Cursor::new(text.to_vec()));
+/// let detected_charsets = xhtmlchardet::detect_peek(&mut reader, None).unwrap();
+/// assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
+///
+/// let mut document = Vec::new();
+/// reader.read_to_end(&mut document).unwrap();
+/// assert_eq!(document, text.to_vec());
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_peek<R: BufRead>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<Vec<String>, io::Error> {
+    detect_peek_with_options(reader, hint, &Options::default())
 }
 
-fn search(needle: &str, haystack: &[u8], descriptor: Option<&Descriptor>) -> Option<String> {
-    let ascii = ASCII_8BIT;
-    let &Descriptor(_, ref width, ref order) = descriptor.unwrap_or(&ascii);
-    let chunk_size = (*width as usize) / 8;
-
-    let mut index = match *order {
-        ByteOrder::NotApplicable | ByteOrder::LittleEndian => 0,
-        ByteOrder::BigEndian => chunk_size - 1,
-        ByteOrder::Unusual2143 => 2,
-        ByteOrder::Unusual3412 => 1,
+/// Attempt to detect the character set of the supplied buffered byte stream without consuming
+/// any of it, using the supplied options.
+///
+/// This is the same as [`detect_peek`](fn.detect_peek.html) with behaviour adjusted by
+/// `options`, including the size of the window examined.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::{BufReader, Cursor};
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::Options;
+///
+/// let text = b"<?charset ISO-8859-1?><channel><title>Example</title></channel>";
+/// let mut reader = BufReader::new(Cursor::new(text.to_vec()));
+/// let options = Options { lenient: true, ..Options::default() };
+/// let detected_charsets = xhtmlchardet::detect_peek_with_options(&mut reader, None, &options);
+/// assert_eq!(detected_charsets.unwrap_or(vec![]), vec!["iso-8859-1".to_string()]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_peek_with_options<R: BufRead>(
+    reader: &mut R,
+    hint: Option<String>,
+    options: &Options,
+) -> Result<Vec<String>, io::Error> {
+    let buf = loop {
+        match reader.fill_buf() {
+            Ok(buf) => break buf,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {} // retry
+            Err(err) => return Err(err),
+        }
     };
+    let bytes = &buf[..buf.len().min(options.window_len())];
 
-    let mut ascii_bytes = Vec::with_capacity(haystack.len() / chunk_size);
-    while index < haystack.len() {
-        ascii_bytes.push(haystack[index]);
-        index += chunk_size;
-    }
-
-    let ascii_haystack = String::from_utf8_lossy(&ascii_bytes);
+    Ok(detect_core(bytes, hint.as_deref(), options).charsets())
+}
 
-    ascii_haystack.find(needle).map(|pos| {
-        // Skip to the matching byte + length of the needle
-        ascii_haystack[pos + needle.len()..]
-            .chars()
-            .skip_while(|char| *char == '"' || *char == '\'')
-            .take_while(|char| *char != '"' && *char != '\'')
-            .collect()
-    })
+/// Attempt to detect the character set of the supplied byte stream when the first four bytes
+/// have already been read from it.
+///
+/// This is useful when the first four bytes have been consumed by some other sniffing step and
+/// `reader` can't be rewound. `peeked` holds the first four bytes of the stream and `reader`
+/// supplies the remainder. Otherwise this is the same as [`detect`](fn.detect.html).
+///
+/// ### Example
+///
+/// ```
+/// use std::io::{Cursor, Read};
+/// extern crate xhtmlchardet;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let mut peeked = [0u8; 4];
+/// text_cursor.read_exact(&mut peeked).unwrap();
+/// let detected_charsets = xhtmlchardet::detect_with_peeked_bom(peeked, &mut text_cursor, None);
+/// assert_eq!(detected_charsets.unwrap_or(vec![]), vec!["iso-8859-1".to_string()]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_with_peeked_bom<R: Read>(
+    peeked: [u8; 4],
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<Vec<String>, io::Error> {
+    detect_with_peeked_bom_with_options(peeked, reader, hint, &Options::default())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Cursor;
+/// Attempt to detect the character set of the supplied byte stream when the first four bytes
+/// have already been read from it, using the supplied options.
+///
+/// This is the same as [`detect_with_peeked_bom`](fn.detect_with_peeked_bom.html) with
+/// behaviour adjusted by `options`. The four peeked bytes count towards
+/// `Options::max_total_read`.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::{Cursor, Read};
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::Options;
+///
+/// let text = b"<?charset ISO-8859-1?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let mut peeked = [0u8; 4];
+/// text_cursor.read_exact(&mut peeked).unwrap();
+/// let options = Options { lenient: true, ..Options::default() };
+/// let detected_charsets =
+///     xhtmlchardet::detect_with_peeked_bom_with_options(peeked, &mut text_cursor, None, &options);
+/// assert_eq!(detected_charsets.unwrap_or(vec![]), vec!["iso-8859-1".to_string()]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_with_peeked_bom_with_options<R: Read>(
+    peeked: [u8; 4],
+    reader: &mut R,
+    hint: Option<String>,
+    options: &Options,
+) -> Result<Vec<String>, io::Error> {
+    let mut buf = vec![0u8; options.window_len()];
+    buf[..4].copy_from_slice(&peeked);
+    let mut reader = reader.take(options.read_limit().saturating_sub(4));
+    let len = 4 + read_prefix(&mut reader, &mut buf[4..])?;
+
+    charsets_for_window(&buf[..len], hint.as_deref(), options)
+}
+
+/// Attempt to detect the character set of the supplied byte stream, also returning how long
+/// detection took.
+///
+/// The duration only covers the detection work, not the time spent reading from `reader`.
+/// Otherwise this is the same as [`detect`](fn.detect.html).
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let (detected_charsets, _duration) = xhtmlchardet::detect_timed(&mut text_cursor, None).unwrap();
+/// assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_timed<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<(Vec<String>, Duration), io::Error> {
+    let options = Options::default();
+    let bytes = read_stream_window(reader, &options)?;
+    let start = Instant::now();
+    let charsets = charsets_for_window(&bytes, hint.as_deref(), &options)?;
+    Ok((charsets, start.elapsed()))
+}
+
+/// Attempt to detect the character set of the supplied byte stream, reporting where each
+/// candidate came from.
+///
+/// Unlike [`detect`](fn.detect.html), short streams are not an error. The candidates are the
+/// same as those returned by `detect`, except that an empty stream is reported as `utf-8` from
+/// `DetectionSource::EmptyInput`, which `DetectionResult::charsets` leaves out.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::DetectionSource;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let result = xhtmlchardet::detect_detailed(&mut text_cursor, None).unwrap();
+/// assert_eq!(result.detections[0].charset, "iso-8859-1");
+/// assert_eq!(result.detections[0].source, DetectionSource::Declaration);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_detailed<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<DetectionResult, io::Error> {
+    detect_detailed_with_options(reader, hint, &Options::default())
+}
+
+/// Attempt to detect the character set of the supplied byte stream using the supplied options,
+/// reporting where each candidate came from.
+///
+/// This is the same as [`detect_detailed`](fn.detect_detailed.html) with behaviour adjusted by
+/// `options`.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::{DetectionWarning, Options};
+///
+/// let text = b"<meta http-equiv=\"Content-Type\" content=\"text/html; &#x63;harset=utf-7\">";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let options = Options { strict: true, ..Options::default() };
+/// let result = xhtmlchardet::detect_detailed_with_options(&mut text_cursor, None, &options).unwrap();
+/// assert_eq!(result.warnings, vec![DetectionWarning::SuspiciousDeclaration]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_detailed_with_options<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+    options: &Options,
+) -> Result<DetectionResult, io::Error> {
+    let mut buf = vec![0u8; options.window_len()];
+    let len = read_prefix(&mut reader.take(options.read_limit()), &mut buf)?;
+
+    Ok(detect_core(&buf[..len], hint.as_deref(), options))
+}
+
+/// Attempt to detect the character set of the supplied byte stream, rejecting text that is
+/// malformed.
+///
+/// This is the same as [`detect`](fn.detect.html) in strict mode, except that an error of kind
+/// `InvalidData` is returned when the byte order mark conflicts with the declaration, as per
+/// `DetectionWarning::ConflictingByteOrderMark`. This suits validators that would rather reject
+/// such text than guess at it.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::{Cursor, ErrorKind};
+/// extern crate xhtmlchardet;
+///
+/// let text = b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00 \x00e\x00n\x00c\x00o\x00d\x00i\x00n\x00g\x00=\x00\"\x00u\x00t\x00f\x00-\x008\x00\"\x00";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let error = xhtmlchardet::detect_strict(&mut text_cursor, None).unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::InvalidData);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_strict<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<Vec<String>, io::Error> {
+    let options = Options {
+        strict: true,
+        ..Options::default()
+    };
+    let result = detect_detailed_with_options(reader, hint, &options)?;
+    if result
+        .warnings
+        .contains(&DetectionWarning::ConflictingByteOrderMark)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "byte order mark conflicts with the declared encoding",
+        ));
+    }
+
+    Ok(result.charsets())
+}
+
+/// Attempt to detect the character set of the supplied byte stream, grouping the candidates by
+/// where they came from.
+///
+/// This presents the result of [`detect_detailed`](fn.detect_detailed.html) so that it's easy to
+/// see when sources disagree, such as the BOM saying one thing and the declaration another. Each
+/// group is in the order the candidates were detected. Sources that produced no candidates are
+/// absent.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::DetectionSource;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let grouped = xhtmlchardet::detect_grouped(&mut text_cursor, Some("windows-1252".to_string())).unwrap();
+/// assert_eq!(grouped[&DetectionSource::Declaration], vec!["iso-8859-1".to_string()]);
+/// assert_eq!(grouped[&DetectionSource::Hint], vec!["windows-1252".to_string()]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_grouped<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<BTreeMap<DetectionSource, Vec<String>>, io::Error> {
+    let mut grouped: BTreeMap<DetectionSource, Vec<String>> = BTreeMap::new();
+    for detection in detect_detailed(reader, hint)?.detections {
+        grouped
+            .entry(detection.source)
+            .or_default()
+            .push(detection.charset);
+    }
+
+    Ok(grouped)
+}
+
+/// Attempt to detect the character set of the supplied byte stream, along with how reliable each
+/// candidate is.
+///
+/// The candidates are the same as those returned by
+/// [`detect_detailed`](fn.detect_detailed.html), most likely first.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::Confidence;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let detected = xhtmlchardet::detect_with_confidence(&mut text_cursor, None).unwrap();
+/// assert_eq!(detected, vec![("iso-8859-1".to_string(), Confidence::Declared)]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_with_confidence<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<Vec<(String, Confidence)>, io::Error> {
+    Ok(detect_detailed(reader, hint)?
+        .detections
+        .into_iter()
+        .map(|detection| (detection.charset, Confidence::from(detection.source)))
+        .collect())
+}
+
+/// Attempt to detect the character set of the supplied byte stream, along with the length in
+/// bytes of its byte order mark.
+///
+/// The candidates are the same as those returned by
+/// [`detect_detailed`](fn.detect_detailed.html). The length is as per
+/// [`bom_length`](fn.bom_length.html), 0 if the text doesn't start with a BOM. Skip that many bytes before
+/// decoding the text to avoid a stray U+FEFF at the start.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// let mut text_cursor = Cursor::new(b"\xEF\xBB\xBF<p>Hello</p>".to_vec());
+/// let (detected_charsets, bom_length) = xhtmlchardet::detect_with_bom_length(&mut text_cursor, None).unwrap();
+/// assert_eq!(detected_charsets, vec!["utf-8".to_string()]);
+/// assert_eq!(bom_length, 3);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_with_bom_length<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<(Vec<String>, usize), io::Error> {
+    let options = Options::default();
+    let mut buf = vec![0u8; options.window_len()];
+    let len = read_prefix(&mut reader.take(options.read_limit()), &mut buf)?;
+    let bytes = &buf[..len];
+
+    let bom_len = detect_structure(bytes).map_or(0, |info| bom_length(&info));
+    Ok((
+        detect_core(bytes, hint.as_deref(), &options).charsets(),
+        bom_len,
+    ))
+}
+
+/// Attempt to detect the character set of the supplied byte stream, along with the offset of the
+/// declaration in the text.
+///
+/// The candidates are the same as those returned by [`detect`](fn.detect.html). The offset is in
+/// bytes from the start of the stream to the start of the `encoding=` or `charset=` declaration,
+/// as per `DetectionResult::declaration_offset`, so that tools re-encoding the text can rewrite or
+/// strip the declaration. It accounts for the width of UTF-16 and UCS-4 text. `None` is returned
+/// if the text has no declaration.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let (detected_charsets, offset) = xhtmlchardet::detect_with_spans(&mut text_cursor, None).unwrap();
+/// assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
+/// assert_eq!(offset, Some(20));
+/// assert!(text[20..].starts_with(b"encoding="));
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_with_spans<R: Read>(
+    reader: &mut R,
+    hint: Option<String>,
+) -> Result<(Vec<String>, Option<usize>), io::Error> {
+    let result = detect_detailed(reader, hint)?;
+    Ok((result.charsets(), result.declaration_offset))
+}
+
+/// Attempt to detect the character set of the supplied byte stream, keeping the bytes that were
+/// read in a buffer supplied by the caller.
+///
+/// The bytes read from `reader` in order to detect the charset are appended to `buf`, which can
+/// then be extended with the rest of the stream and handed to a parser, so nothing is lost when
+/// `reader` can't be rewound. Only the appended bytes are examined. The candidates are the same
+/// as those returned by [`detect_detailed`](fn.detect_detailed.html).
+///
+/// ### Example
+///
+/// ```
+/// use std::io::{Cursor, Read};
+/// extern crate xhtmlchardet;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let mut text_cursor = Cursor::new(text.to_vec());
+/// let mut buf = Vec::new();
+/// let detected_charsets = xhtmlchardet::detect_into(&mut text_cursor, &mut buf, None).unwrap();
+/// assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
+///
+/// text_cursor.read_to_end(&mut buf).unwrap();
+/// assert_eq!(buf, text.to_vec());
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_into<R: Read>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    hint: Option<String>,
+) -> Result<Vec<String>, io::Error> {
+    let options = Options::default();
+    let start = buf.len();
+    reader.take(options.read_limit()).read_to_end(buf)?;
+
+    Ok(detect_core(&buf[start..], hint.as_deref(), &options).charsets())
+}
+
+/// Attempt to detect the character set of the supplied bytes.
+///
+/// This is the same as [`detect`](fn.detect.html) but for text that is already in memory. Only
+/// the bytes in the window of the default options, as per
+/// [`Options::window_len`](struct.Options.html#method.window_len), are examined.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let detected_charsets = xhtmlchardet::detect_slice(text, None);
+/// assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
+/// ```
+pub fn detect_slice(bytes: &[u8], hint: Option<String>) -> Vec<String> {
+    detect_slice_with_options(bytes, hint, &Options::default())
+}
+
+/// Attempt to detect the character set of the supplied bytes using the supplied options.
+///
+/// This is the same as [`detect_slice`](fn.detect_slice.html) with behaviour adjusted by
+/// `options`, including the size of the window examined.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::Options;
+///
+/// let text = b"<?charset ISO-8859-1?><channel><title>Example</title></channel>";
+/// let options = Options { lenient: true, ..Options::default() };
+/// let detected_charsets = xhtmlchardet::detect_slice_with_options(text, None, &options);
+/// assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
+/// ```
+pub fn detect_slice_with_options(
+    bytes: &[u8],
+    hint: Option<String>,
+    options: &Options,
+) -> Vec<String> {
+    let len = bytes.len().min(options.window_len());
+    detect_core(&bytes[..len], hint.as_deref(), options).charsets()
+}
+
+/// Attempt to detect the character set of the supplied bytes, with the same signature as
+/// [`detect`](fn.detect.html).
+///
+/// This saves wrapping text that is already in memory, such as a buffered HTTP body, in a
+/// `Cursor`. As with `detect`, an error of kind `UnexpectedEof` is returned if `bytes` is one to
+/// three bytes long, while empty text, or four bytes that don't start with a byte order mark, have
+/// no candidates. Use [`detect_slice`](fn.detect_slice.html) if short text shouldn't be an error.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel><title>Example</title></channel>";
+/// let detected_charsets = xhtmlchardet::detect_bytes(text, None);
+/// assert_eq!(detected_charsets.unwrap_or(vec![]), vec!["iso-8859-1".to_string()]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_bytes(mut bytes: &[u8], hint: Option<String>) -> Result<Vec<String>, io::Error> {
+    detect_stream(&mut bytes, hint.as_deref(), &Options::default())
+}
+
+/// Determine whether two texts are likely to share an encoding.
+///
+/// The most likely charset of each, as per [`detect_slice`](fn.detect_slice.html), is compared.
+/// How the charset was determined doesn't matter, so text with a UTF-8 BOM has the same encoding
+/// as BOM-less text that is valid UTF-8. Empty text is valid UTF-8 and is treated as such. Two
+/// texts whose charsets can't be determined are considered to share an encoding, but one that
+/// can't be determined doesn't share an encoding with one that can.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// assert!(xhtmlchardet::same_encoding(b"\xEF\xBB\xBF<p>caf\xC3\xA9</p>", b"<p>Hello</p>"));
+/// assert!(!xhtmlchardet::same_encoding(
+///     b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>",
+///     b"<p>Hello</p>"
+/// ));
+/// ```
+pub fn same_encoding(a: &[u8], b: &[u8]) -> bool {
+    most_likely_charset(a) == most_likely_charset(b)
+}
+
+// The first candidate of `detect_slice`, including the `utf-8` of empty text
+fn most_likely_charset(bytes: &[u8]) -> Option<String> {
+    let len = bytes.len().min(PREFIX_LEN);
+    let result = detect_core(&bytes[..len], None, &Options::default());
+    result
+        .detections
+        .into_iter()
+        .next()
+        .map(|detection| detection.charset)
+}
+
+/// Attempt to detect the character set of the supplied byte stream from its byte order mark
+/// alone.
+///
+/// Only the first four bytes of `reader` are read, making this much cheaper than
+/// [`detect`](fn.detect.html) when only certainty from a byte order mark matters. `None` is
+/// returned if there is no recognisable byte order mark.
+///
+/// ### Example
+///
+/// ```
+/// use std::io::Cursor;
+/// extern crate xhtmlchardet;
+///
+/// let mut text_cursor = Cursor::new(b"\xEF\xBB\xBF<?xml version=\"1.0\"?>".to_vec());
+/// let detected_charset = xhtmlchardet::detect_bom_only(&mut text_cursor).unwrap();
+/// assert_eq!(detected_charset, Some("utf-8".to_string()));
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_bom_only<R: Read>(reader: &mut R) -> Result<Option<String>, io::Error> {
+    let mut buf = [0u8; 4];
+    let len = read_prefix(reader, &mut buf)?;
+
+    Ok(bom_charset(detect_prefix_descriptor(&buf[..len]).as_ref()).map(str::to_string))
+}
+
+/// Determine what the first four bytes of `bytes` reveal about how the text is encoded.
+///
+/// This recognises byte order marks and the patterns formed by the start of a declaration, as per
+/// [appendix F of the XML specification][xml]. `None` is returned if nothing is recognised,
+/// including when `bytes` is shorter than four bytes.
+///
+/// [xml]: https://www.w3.org/TR/xml/#sec-guessing-no-ext-info
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// let info = xhtmlchardet::detect_structure(b"\xFF\xFE<\x00?\x00").unwrap();
+/// assert_eq!(info.width(), 16);
+/// assert_eq!(xhtmlchardet::bom_length(&info), 2);
+/// ```
+pub fn detect_structure(bytes: &[u8]) -> Option<StructuralInfo> {
+    detect_prefix_descriptor(bytes).map(|descriptor| StructuralInfo { descriptor })
+}
+
+/// Determine which byte order mark, if any, `bytes` starts with.
+///
+/// Only the first four bytes are examined, making this a cheap check when only a byte order mark
+/// matters. `None` is returned if there is no byte order mark, including when `bytes` is shorter
+/// than four bytes.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// use xhtmlchardet::BomKind;
+///
+/// let bom = xhtmlchardet::detect_bom(b"\xFF\xFE<\x00?\x00").unwrap();
+/// assert_eq!(bom, BomKind::Utf16Le);
+/// assert_eq!(bom.length(), 2);
+/// assert_eq!(bom.charset(), Some("utf-16le"));
+/// ```
+pub fn detect_bom(bytes: &[u8]) -> Option<BomKind> {
+    match detect_prefix_descriptor(bytes)? {
+        UTF_8 => Some(BomKind::Utf8),
+        UTF_16_LE => Some(BomKind::Utf16Le),
+        UTF_16_BE => Some(BomKind::Utf16Be),
+        UCS_4_LE => Some(BomKind::Ucs4Le),
+        UCS_4_BE => Some(BomKind::Ucs4Be),
+        UCS_4_2143 => Some(BomKind::Ucs4Unusual2143),
+        UCS_4_3412 => Some(BomKind::Ucs4Unusual3412),
+        UTF_7 => Some(BomKind::Utf7),
+        _ => None,
+    }
+}
+
+/// The length in bytes of the byte order mark at the start of the text described by `info`.
+///
+/// This is 3 for UTF-8, 2 for UTF-16, 4 for UCS-4 and 0 when the text has no byte order mark.
+/// It's also 0 for UTF-7, as the last character of its signature shares bits with the next
+/// character, so the signature can't be skipped without decoding.
+pub fn bom_length(info: &StructuralInfo) -> usize {
+    descriptor_bom_length(&info.descriptor)
+}
+
+fn descriptor_bom_length(descriptor: &Descriptor) -> usize {
+    match *descriptor {
+        UTF_8 => 3,
+        UTF_16_BE | UTF_16_LE => 2,
+        UCS_4_BE | UCS_4_LE | UCS_4_2143 | UCS_4_3412 => 4,
+        _ => 0,
+    }
+}
+
+/// Read from `reader` until `buf` is full or the end of the stream is reached, returning the
+/// number of bytes read.
+#[cfg(feature = "std")]
+fn read_prefix<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, io::Error> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break, // eof
+            Ok(n) => len += n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {} // retry
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(len)
+}
+
+// Run the default chain, which is what all of the detection functions come down to
+fn detect_core(bytes: &[u8], hint: Option<&str>, options: &Options) -> DetectionResult {
+    DetectorChain::default().run(bytes, hint, options)
+}
+
+// Doubly encoded UTF-8 shows up as the UTF-8 encoding of a Latin-1 lead byte, such as Ã (C3 83),
+// followed by the UTF-8 encoding of a continuation byte as decoded by windows-1252. Continuation
+// bytes are either Latin-1 characters (C2 80 to C2 BF) or windows-1252 punctuation, such as €, ’
+// and ™ (E2 80 to E2 84).
+fn has_mojibake(bytes: &[u8]) -> bool {
+    bytes.windows(4).any(|window| {
+        matches!(
+            *window,
+            [0xC3, 0x82..=0xAF, 0xC2, 0x80..=0xBF] | [0xC3, 0x82..=0xAF, 0xE2, 0x80..=0x84]
+        )
+    })
+}
+
+// Find the primary language subtag of the first lang="" or xml:lang="" attribute, lowercased
+fn search_language(ascii_bytes: &[u8]) -> Option<String> {
+    let lowercase = ascii_bytes.to_ascii_lowercase();
+    let needle = b"lang=";
+    let mut index = 0;
+    while let Some(pos) = find(&lowercase[index..], needle) {
+        let start = index + pos;
+        index = start + needle.len();
+        // Skip the likes of hreflang=""
+        let is_attribute = match start.checked_sub(1).map(|before| lowercase[before]) {
+            Some(b':') => lowercase[..start - 1].ends_with(b"xml"),
+            Some(byte) => byte.is_ascii_whitespace(),
+            None => false,
+        };
+        if !is_attribute {
+            continue;
+        }
+
+        let subtag: Vec<u8> = lowercase[index..]
+            .iter()
+            .cloned()
+            .skip_while(|byte| QUOTES.contains(byte))
+            .take_while(u8::is_ascii_alphabetic)
+            .collect();
+        if !subtag.is_empty() {
+            return String::from_utf8(subtag).ok();
+        }
+    }
+
+    None
+}
+
+// Guess the legacy multi-byte charset of text that is ASCII mixed with well formed sequences of
+// that charset. Japanese text is recognised by its kana, which make up a good part of any prose.
+// Otherwise GB18030 is preferred as its byte ranges are the widest, so text that is well formed in
+// it and something else is more likely to be Chinese.
+fn guess_multibyte_charset(bytes: &[u8], cut_short: bool) -> Option<&'static str> {
+    let counts = |charset| multibyte_counts(bytes, charset, cut_short);
+    let japanese = ["euc-jp", "shift_jis"].iter().find(|&&charset| {
+        counts(charset).is_some_and(|(multibyte, kana)| multibyte > 0 && kana * 4 >= multibyte)
+    });
+
+    japanese
+        .or_else(|| {
+            ["gb18030", "shift_jis", "euc-jp"]
+                .iter()
+                .find(|&&charset| counts(charset).is_some_and(|(multibyte, _)| multibyte > 0))
+        })
+        .cloned()
+}
+
+// The number of multi-byte characters in the text, and how many of those are kana, if the text is
+// ASCII mixed with well formed characters of `charset`. A character cut off at the end is tolerated
+// when the text was cut short.
+fn multibyte_counts(bytes: &[u8], charset: &str, cut_short: bool) -> Option<(usize, usize)> {
+    let (mut multibyte, mut kana) = (0, 0);
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        // The length of the character, 0 if it's cut off by the end of the text
+        let len = match (charset, rest) {
+            (_, &[0x00..=0x7F, ..]) => 1,
+            ("gb18030", &[0x81..=0xFE, 0x40..=0x7E | 0x80..=0xFE, ..]) => 2,
+            ("gb18030", &[0x81..=0xFE, 0x30..=0x39, 0x81..=0xFE, 0x30..=0x39, ..]) => 4,
+            ("gb18030", &[0x81..=0xFE])
+            | ("gb18030", &[0x81..=0xFE, 0x30..=0x39])
+            | ("gb18030", &[0x81..=0xFE, 0x30..=0x39, 0x81..=0xFE]) => 0,
+            // Half-width katakana are a single byte
+            ("shift_jis", &[0xA1..=0xDF, ..]) => 1,
+            ("shift_jis", &[0x81..=0x9F | 0xE0..=0xFC, 0x40..=0x7E | 0x80..=0xFC, ..]) => 2,
+            ("shift_jis", &[0x81..=0x9F | 0xE0..=0xFC]) => 0,
+            ("euc-jp", &[0x8E, 0xA1..=0xDF, ..]) | ("euc-jp", &[0xA1..=0xFE, 0xA1..=0xFE, ..]) => 2,
+            ("euc-jp", &[0x8F, 0xA1..=0xFE, 0xA1..=0xFE, ..]) => 3,
+            ("euc-jp", &[0x8E])
+            | ("euc-jp", &[0x8F])
+            | ("euc-jp", &[0x8F, 0xA1..=0xFE])
+            | ("euc-jp", &[0xA1..=0xFE]) => 0,
+            _ => return None,
+        };
+        if len == 0 {
+            return if cut_short {
+                Some((multibyte, kana))
+            } else {
+                None
+            };
+        }
+
+        if len > 1 {
+            multibyte += 1;
+        }
+        // Hiragana and katakana, as opposed to kanji, which are shared with Chinese
+        if matches!(
+            (charset, rest[0], rest.get(1)),
+            ("euc-jp", 0xA4..=0xA5, _)
+                | ("shift_jis", 0x82, Some(&(0x9F..=0xF1)))
+                | ("shift_jis", 0x83, Some(&(0x40..=0x96)))
+        ) {
+            kana += 1;
+        }
+        rest = &rest[len..];
+    }
+
+    Some((multibyte, kana))
+}
+
+// The legacy charsets typically used for a language, most common first
+fn language_charsets(language: &str) -> &'static [&'static str] {
+    match language {
+        "ja" => &["shift_jis", "euc-jp"],
+        "ko" => &["euc-kr"],
+        "zh" => &["gb18030", "big5"],
+        "ru" | "uk" | "be" | "bg" | "sr" | "mk" => &["windows-1251", "koi8-r"],
+        "el" => &["windows-1253", "iso-8859-7"],
+        "he" => &["windows-1255", "iso-8859-8"],
+        "ar" => &["windows-1256", "iso-8859-6"],
+        "tr" => &["windows-1254", "iso-8859-9"],
+        _ => &[],
+    }
+}
+
+// Well formed multi-byte UTF-8 is unlikely to occur by chance in text in a single byte charset, so
+// finding some outweighs a declaration of one
+fn multibyte_utf8_outweighs(charset: &str, bytes: &[u8], cut_short: bool) -> bool {
+    let single_byte = charset == "ascii"
+        || charset.starts_with("iso-8859-")
+        || charset.starts_with("windows-125");
+    // A UTF-8 BOM is a source of its own rather than part of the content
+    let content = if bytes.starts_with(b"\xEF\xBB\xBF") {
+        &bytes[3..]
+    } else {
+        bytes
+    };
+    single_byte
+        && match utf8_prefix_len(content, cut_short) {
+            // Ignore a sequence cut short at the end, which could as easily be a single byte
+            Some(len) => !content[..len].is_ascii(),
+            None => false,
+        }
+}
+
+// The length of `bytes` when it's valid UTF-8. When `cut_short` the text may continue beyond
+// `bytes`, so a character cut short at the end is allowed for. Returns `None` if `bytes` isn't
+// UTF-8.
+fn utf8_prefix_len(bytes: &[u8], cut_short: bool) -> Option<usize> {
+    match core::str::from_utf8(bytes) {
+        Ok(_) => Some(bytes.len()),
+        // error_len is None when the input ends part way through a sequence
+        Err(err) if cut_short && err.error_len().is_none() => Some(err.valid_up_to()),
+        Err(_) => None,
+    }
+}
+
+// Work out how the text is encoded from its first four bytes or, failing that, from the
+// whitespace and first < at the start of the text
+fn detect_descriptor(bytes: &[u8]) -> Option<Descriptor> {
+    detect_prefix_descriptor(bytes).or_else(|| detect_descriptor_from_first_tag(bytes))
+}
+
+// Text without a BOM can start with something other than the <? the first four bytes are
+// matched against, such as blank lines or a comment before the declaration. Find the width and
+// byte order in which the text is only whitespace up to its first <, with the other bytes of each
+// character zero. Text that is 8-bit from the start isn't matched.
+fn detect_descriptor_from_first_tag(bytes: &[u8]) -> Option<Descriptor> {
+    // The descriptor, the width in bytes and the position of the ASCII byte in each character
+    let candidates = [
+        (ASCII_16BIT_LE, 2, 0),
+        (ASCII_16BIT_BE, 2, 1),
+        (ASCII_32BIT_LE, 4, 0),
+        (ASCII_32BIT_BE, 4, 3),
+    ];
+
+    candidates
+        .iter()
+        .find(|&&(_, width, position)| {
+            for unit in bytes.chunks_exact(width) {
+                let high_bytes_zero = unit
+                    .iter()
+                    .enumerate()
+                    .all(|(index, &byte)| index == position || byte == 0);
+                if !high_bytes_zero {
+                    return false;
+                }
+                match unit[position] {
+                    b'<' => return true,
+                    byte if byte.is_ascii_whitespace() => {}
+                    _ => return false,
+                }
+            }
+            false
+        })
+        .map(|(descriptor, _, _)| descriptor.clone())
+}
+
+fn detect_prefix_descriptor(bytes: &[u8]) -> Option<Descriptor> {
+    if bytes.len() >= 4 {
+        detect_byte_order_mark(&Bom(bytes[0], bytes[1], bytes[2], bytes[3]))
+    } else {
+        None
+    }
+}
+
+fn detect_byte_order_mark(bom: &Bom) -> Option<Descriptor> {
+    // Can do below without the Bom type if slice pattern syntax becomes non-experimental
+    // let possible = match first_four_bytes {
+    //     // With Byte Order Mark
+    //     [0x00, 0x00, 0xFE, 0xFF]             => Some("UCS-4BE"),
+    //     [0xFF, 0xFE, 0x00, 0x00]             => Some("UCS-4LE"),
+    //     [0x00, 0x00, 0xFF, 0xFE]             => Some("UCS-4OE"),
+    //     [0xFE, 0xFF, 0x00, 0x00]             => Some("UCS-4EO"),
+    //     [0xFE, 0xFF, c, d] if c > 0 && d > 0 => Some("UTF-16BE"),
+    //     [0xFF, 0xFE, c, d] if c > 0 && d > 0 => Some("UCS-16LE"),
+    //     [0xEF, 0xBB, 0xBF, _   ]             => Some("UTF-8"),
+
+    //     //  Without Byte Order Mark
+    //     [0x00, 0x00, 0x00, 0x3C] |
+    //     [0x3C, 0x00, 0x00, 0x00] |
+    //     [0x00, 0x00, 0x3C, 0x00] |
+    //     [0x00, 0x3C, 0x00, 0x00]             => Some("32-bit"),
+    //     [0x00, 0x3C, 0x00, 0x3F]             => Some("16-bit Big Endian"),
+    //     [0x3C, 0x00, 0x3F, 0x00]             => Some("16-bit Little Endian"),
+    //     [0x3C, 0x3F, 0x78, 0x6D]             => Some("8-bit"),
+    //     [0x4C, 0x6F, 0xA7, 0x94]             => Some("EBCDIC"),
+    //     // This may be UTF-8 without an encoding declaration as this is not required
+    //     // for UTF-8
+    //     _                                    => Some("Other"),
+    // };
+
+    // http://www.w3.org/TR/2004/REC-xml-20040204/#sec-guessing-no-ext-info
+    match *bom {
+        // With Byte Order Mark
+        Bom(0x00, 0x00, 0xFE, 0xFF) => Some(UCS_4_BE),
+        Bom(0xFF, 0xFE, 0x00, 0x00) => Some(UCS_4_LE),
+        Bom(0x00, 0x00, 0xFF, 0xFE) => Some(UCS_4_2143),
+        Bom(0xFE, 0xFF, 0x00, 0x00) => Some(UCS_4_3412),
+        Bom(0xFE, 0xFF, c, d) if c > 0 || d > 0 => Some(UTF_16_BE),
+        Bom(0xFF, 0xFE, c, d) if c > 0 || d > 0 => Some(UTF_16_LE),
+        Bom(0xEF, 0xBB, 0xBF, _) => Some(UTF_8),
+        // The UTF-7 signature, +/v, is followed by a character that also holds the start of the
+        // next character
+        Bom(b'+', b'/', b'v', b'8') | Bom(b'+', b'/', b'v', b'9') => Some(UTF_7),
+        Bom(b'+', b'/', b'v', b'+') | Bom(b'+', b'/', b'v', b'/') => Some(UTF_7),
+
+        //  Without Byte Order Mark
+        Bom(0x00, 0x00, 0x00, 0x3C) => Some(ASCII_32BIT_BE),
+        Bom(0x3C, 0x00, 0x00, 0x00) => Some(ASCII_32BIT_LE),
+        Bom(0x00, 0x00, 0x3C, 0x00) => Some(Descriptor(
+            Flavour::Unknown,
+            Width::ThirtyTwo,
+            ByteOrder::Unusual2143,
+        )),
+        Bom(0x00, 0x3C, 0x00, 0x00) => Some(Descriptor(
+            Flavour::Unknown,
+            Width::ThirtyTwo,
+            ByteOrder::Unusual3412,
+        )),
+        Bom(0x00, 0x3C, 0x00, 0x3F) => Some(ASCII_16BIT_BE),
+        Bom(0x3C, 0x00, 0x3F, 0x00) => Some(ASCII_16BIT_LE),
+        Bom(0x3C, 0x3F, 0x78, 0x6D) => Some(ASCII_8BIT),
+        Bom(0x4C, 0x6F, 0xA7, 0x94) => Some(EBCDIC),
+        // This may be UTF-8 without an encoding declaration as this is not required
+        // for UTF-8
+        _ => None,
+    }
+}
+
+fn bom_charset(descriptor: Option<&Descriptor>) -> Option<&'static str> {
+    match descriptor {
+        Some(&UCS_4_LE) => Some("ucs-4le"),
+        Some(&UCS_4_BE) => Some("ucs-4be"),
+        Some(&UTF_16_LE) => Some("utf-16le"),
+        Some(&UTF_16_BE) => Some("utf-16be"),
+        Some(&Descriptor(Flavour::Utf, Width::Eight, _)) => Some("utf-8"),
+        Some(&UTF_7) => Some("utf-7"),
+        Some(&EBCDIC) => Some("ebcdic"),
+        _ => None,
+    }
+}
+
+// Whether the declared charset can't be read from text starting with the byte order mark
+// described by `descriptor`, because the BOM is for a different width of Unicode or the
+// declaration isn't Unicode at all
+fn conflicts_with_bom(declared: &str, descriptor: Option<&Descriptor>) -> bool {
+    let bom_width = match descriptor {
+        Some(descriptor) if descriptor_bom_length(descriptor) > 0 => descriptor.1,
+        _ => return false,
+    };
+    let declared_width = match resolve_label(declared).as_deref() {
+        Some("utf-8") => Some(Width::Eight),
+        Some("utf-16") | Some("utf-16le") | Some("utf-16be") => Some(Width::Sixteen),
+        Some("utf-32") | Some("utf-32le") | Some("utf-32be") | Some("ucs-4le")
+        | Some("ucs-4be") => Some(Width::ThirtyTwo),
+        _ => None,
+    };
+    declared_width != Some(bom_width)
+}
+
+// Whether the text consists of one or more NUL bytes and nothing else
+fn is_nul_padding(bytes: &[u8]) -> bool {
+    !bytes.is_empty() && bytes.iter().all(|&byte| byte == 0)
+}
+
+// Whether a normalised charset name is an EBCDIC code page, such as cp037 or ibm-1047
+fn is_ebcdic(charset: &str) -> bool {
+    const CODE_PAGES: &[u16] = &[
+        37, 273, 277, 278, 280, 284, 285, 290, 297, 420, 424, 500, 870, 871, 875, 1025, 1026, 1047,
+        1140, 1141, 1142, 1143, 1144, 1145, 1146, 1147, 1148, 1149,
+    ];
+
+    if charset.starts_with("ebcdic") {
+        return true;
+    }
+    let number = ["cp", "ibm-", "ibm"]
+        .iter()
+        .find_map(|prefix| charset.strip_prefix(prefix))
+        .and_then(|number| number.parse().ok());
+    match number {
+        Some(number) => CODE_PAGES.contains(&number),
+        None => false,
+    }
+}
+
+// Split a hint into its normalised charsets, most preferred first. As well as a single name the
+// hint may be an Accept-Charset style list, like "utf-8, iso-8859-1;q=0.5".
+fn parse_hint(hint: &str) -> Vec<String> {
+    let mut charsets: Vec<(String, f32)> = hint
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let name = parts.next().unwrap_or("").trim();
+            let quality = parts
+                .filter_map(|parameter| {
+                    let parameter = parameter.trim();
+                    match parameter.get(..2) {
+                        Some(key) if key.eq_ignore_ascii_case("q=") => {
+                            parameter[2..].trim().parse().ok()
+                        }
+                        _ => None,
+                    }
+                })
+                .next()
+                .unwrap_or(1.0);
+
+            // A quality of 0 means not acceptable
+            if name.is_empty() || name == "*" || quality <= 0.0 {
+                None
+            } else {
+                Some((normalise(name), quality))
+            }
+        })
+        .collect();
+
+    // The sort is stable so equally preferred charsets stay in the order given
+    charsets.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+    charsets.into_iter().map(|(name, _)| name).collect()
+}
+
+// The name of a UTF-16 or UCS-4 charset without its byte order
+fn bare_unicode_width(charset: &str) -> &str {
+    match charset {
+        "utf-16le" | "utf-16be" => "utf-16",
+        "ucs-4le" | "ucs-4be" => "utf-32",
+        _ => charset,
+    }
+}
+
+// The UTF-32 name for a UCS-4 charset with a byte order
+fn utf32_label(charset: &str) -> &str {
+    match charset {
+        "ucs-4le" => "utf-32le",
+        "ucs-4be" => "utf-32be",
+        _ => charset,
+    }
+}
+
+fn normalise<S: AsRef<str>>(encoding: S) -> String {
+    let encoding = encoding.as_ref().to_lowercase();
+    // Sloppy configuration sometimes leaves a stray leading dot, as in charset=.utf-8
+    let encoding = encoding.strip_prefix('.').unwrap_or(&encoding);
+    // or names a Windows code page with a scheme-like prefix, as in cp:1252
+    let code_page = ["cp:", "windows:"]
+        .iter()
+        .find_map(|prefix| encoding.strip_prefix(prefix))
+        .filter(|number| {
+            matches!(
+                *number,
+                "1250" | "1251" | "1252" | "1253" | "1254" | "1255" | "1256" | "1257" | "1258"
+            )
+        });
+    if let Some(number) = code_page {
+        return format!("windows-{}", number);
+    }
+
+    // Known labels resolve to their canonical name and anything else passes through unchanged
+    canonical_name(encoding).map_or_else(|| encoding.to_string(), str::to_string)
+}
+
+// Resolve the byte order of a bare utf-16. When the order can't be determined both orders are
+// returned, big endian first as that's the default without a BOM (RFC 2781).
+fn endianify(encoding: &str, descriptor: Option<&Descriptor>, bytes: &[u8]) -> Vec<String> {
+    let ascii = ASCII_8BIT;
+    let Descriptor(_, _, ref order) = *descriptor.unwrap_or(&ascii);
+
+    match encoding {
+        "utf-16" => match *order {
+            ByteOrder::LittleEndian => vec!["utf-16le".to_string()],
+            ByteOrder::BigEndian => vec!["utf-16be".to_string()],
+            _ => match guess_utf16_byte_order(bytes) {
+                Some(ByteOrder::LittleEndian) => vec!["utf-16le".to_string()],
+                Some(ByteOrder::BigEndian) => vec!["utf-16be".to_string()],
+                _ => vec!["utf-16be".to_string(), "utf-16le".to_string()],
+            },
+        },
+        _ => vec![encoding.to_string()],
+    }
+}
+
+// Guess the byte order of UTF-16 text from where its NUL bytes fall. Mostly ASCII text has NUL
+// high bytes, which come first in big endian text and second in little endian text.
+fn guess_utf16_byte_order(bytes: &[u8]) -> Option<ByteOrder> {
+    let (mut even, mut odd) = (0, 0);
+    for (index, _) in bytes.iter().enumerate().filter(|&(_, &byte)| byte == 0) {
+        if index % 2 == 0 {
+            even += 1;
+        } else {
+            odd += 1;
+        }
+    }
+
+    if even > odd * 2 {
+        Some(ByteOrder::BigEndian)
+    } else if odd > even * 2 {
+        Some(ByteOrder::LittleEndian)
+    } else {
+        None
+    }
+}
+
+// Render the text as 8-bit bytes that can be searched for ASCII declarations. UTF-16 and UCS-4
+// text, as described by `descriptor`, is decoded to UTF-8 so that non-ASCII characters can't be
+// mistaken for the ASCII characters in their low bytes. Malformed characters become U+FFFD.
+fn ascii_bytes(haystack: &[u8], descriptor: Option<&Descriptor>) -> Vec<u8> {
+    let ascii = ASCII_8BIT;
+    let Descriptor(_, width, order) = *descriptor.unwrap_or(&ascii);
+
+    match width {
+        Width::Eight => haystack.to_vec(),
+        Width::Sixteen | Width::ThirtyTwo => wide_chars(haystack, width, order)
+            .into_iter()
+            .map(|(c, _)| c)
+            .collect::<String>()
+            .into_bytes(),
+    }
+}
+
+// Map an offset into the output of `ascii_bytes` back to the offset of the same character in
+// `haystack`
+fn input_offset(haystack: &[u8], descriptor: Option<&Descriptor>, ascii_offset: usize) -> usize {
+    let ascii = ASCII_8BIT;
+    let Descriptor(_, width, order) = *descriptor.unwrap_or(&ascii);
+
+    match width {
+        Width::Eight => ascii_offset,
+        Width::Sixteen | Width::ThirtyTwo => {
+            let (mut output, mut input) = (0, 0);
+            for (c, len) in wide_chars(haystack, width, order) {
+                if output >= ascii_offset {
+                    break;
+                }
+                output += c.len_utf8();
+                input += len;
+            }
+            input
+        }
+    }
+}
+
+// Decode UTF-16 or UCS-4 text into its characters, each with the number of bytes it took up.
+// Malformed characters become U+FFFD. 8-bit text is taken to be ISO-8859-1.
+fn wide_chars(haystack: &[u8], width: Width, order: ByteOrder) -> Vec<(char, usize)> {
+    match width {
+        Width::Sixteen => {
+            let units = haystack.chunks_exact(2).map(|unit| match order {
+                ByteOrder::LittleEndian => u16::from_le_bytes([unit[0], unit[1]]),
+                _ => u16::from_be_bytes([unit[0], unit[1]]),
+            });
+            char::decode_utf16(units)
+                .map(|c| match c {
+                    Ok(c) => (c, c.len_utf16() * 2),
+                    Err(_) => (char::REPLACEMENT_CHARACTER, 2),
+                })
+                .collect()
+        }
+        Width::Eight => haystack.iter().map(|&byte| (char::from(byte), 1)).collect(),
+        Width::ThirtyTwo => haystack
+            .chunks_exact(4)
+            .map(|unit| {
+                let value = match order {
+                    ByteOrder::LittleEndian => {
+                        u32::from_le_bytes([unit[0], unit[1], unit[2], unit[3]])
+                    }
+                    ByteOrder::Unusual2143 => {
+                        u32::from_be_bytes([unit[1], unit[0], unit[3], unit[2]])
+                    }
+                    ByteOrder::Unusual3412 => {
+                        u32::from_be_bytes([unit[2], unit[3], unit[0], unit[1]])
+                    }
+                    ByteOrder::BigEndian | ByteOrder::NotApplicable => {
+                        u32::from_be_bytes([unit[0], unit[1], unit[2], unit[3]])
+                    }
+                };
+                let c = char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER);
+                (c, 4)
+            })
+            .collect(),
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// Search the text for an encoding="" or charset="" declaration. The offset of the declaration is
+// that of its first byte in `bytes`.
+fn search_declaration(
+    bytes: &[u8],
+    descriptor: Option<&Descriptor>,
+    options: &Options,
+) -> Option<Declared> {
+    let mut ascii_bytes = ascii_bytes(bytes, descriptor);
+    mask_xml_processing_instructions(&mut ascii_bytes);
+    if options.content_kind == ContentKind::Html {
+        if options.strict {
+            mask_php_blocks(&mut ascii_bytes);
+        }
+        // A meta charset in the body is too late to be used
+        if let Some(end) = find(&ascii_bytes.to_ascii_lowercase(), b"</head") {
+            ascii_bytes.truncate(end);
+        }
+    }
+
+    let quotes = if options.lenient {
+        LENIENT_QUOTES
+    } else {
+        QUOTES
+    };
+    let encoding = if options.strict {
+        // Only the XML declaration can declare an encoding, not an attribute of some element,
+        // like <svg encoding="...">
+        xml_declaration(&ascii_bytes).and_then(|(start, declaration)| {
+            search("encoding", declaration, quotes).map(|declared| Declared {
+                offset: start + declared.offset,
+                ..declared
+            })
+        })
+    } else {
+        search("encoding", &ascii_bytes, quotes)
+    };
+    let charset = search("charset", &ascii_bytes, quotes);
+
+    // The declaration that comes first wins, as per the HTML specification for multiple metas
+    let declared = match (encoding, charset) {
+        (Some(encoding), Some(charset)) if charset.offset < encoding.offset => Some(charset),
+        (Some(encoding), _) => Some(encoding),
+        (None, charset) => charset,
+    };
+    declared.map(|declared| Declared {
+        offset: input_offset(bytes, descriptor, declared.offset),
+        ..declared
+    })
+}
+
+// The offset of the XML declaration, from <?xml to ?>, along with the declaration itself, if the
+// text has one
+fn xml_declaration(ascii_bytes: &[u8]) -> Option<(usize, &[u8])> {
+    let start = find(ascii_bytes, b"<?xml")?;
+    let rest = &ascii_bytes[start..];
+    match rest.get(5) {
+        Some(byte) if byte.is_ascii_whitespace() => {}
+        _ => return None,
+    }
+    let end = find(rest, b"?>").map_or(rest.len(), |end| end + 2);
+    Some((start, &rest[..end]))
+}
+
+// Search for a declaration in text that may start with a few garbled bytes, such as a mangled
+// BOM, that prevented the first four bytes being interpreted. A BOM a little way in is used if
+// present, otherwise the whole text is searched as 8-bit. Returns the offset of the BOM, if any,
+// along with the descriptor it implies.
+fn search_past_leading_junk(
+    bytes: &[u8],
+    options: &Options,
+) -> Option<(usize, Option<Descriptor>, Declared)> {
+    for offset in 1..=LEADING_JUNK_LIMIT.min(bytes.len()) {
+        let descriptor = detect_prefix_descriptor(&bytes[offset..]);
+        let is_bom = matches!(
+            descriptor,
+            Some(UCS_4_BE)
+                | Some(UCS_4_LE)
+                | Some(UCS_4_2143)
+                | Some(UCS_4_3412)
+                | Some(UTF_16_BE)
+                | Some(UTF_16_LE)
+                | Some(UTF_8)
+        );
+        if is_bom {
+            if let Some(declared) =
+                search_declaration(&bytes[offset..], descriptor.as_ref(), options)
+            {
+                return Some((offset, descriptor, declared));
+            }
+        }
+    }
+
+    // The first four bytes were misleading so don't rely on them
+    search_declaration(bytes, None, options).map(|declared| (0, None, declared))
+}
+
+// The value of a declaration found by `search`
+struct Declared {
+    // The name of the declaration, encoding or charset
+    name: &'static str,
+    value: String,
+    // Whether the closing quote was missing
+    truncated: bool,
+    // Where the name of the declaration, as in encoding=, starts
+    offset: usize,
+}
+
+// The quotes that delimit declared values
+const QUOTES: &[u8] = b"\"'";
+// Backticks are also used by some broken templating engines
+const LENIENT_QUOTES: &[u8] = b"\"'`";
+
+// Find the first `name=` in the text, allowing whitespace either side of the `=` as XML does, as
+// in encoding = "utf-8". Returns the offset of the name along with that of the value that follows.
+fn find_attribute_value(ascii_bytes: &[u8], name: &[u8]) -> Option<(usize, usize)> {
+    let skip_whitespace = |index: usize| {
+        index
+            + ascii_bytes[index..]
+                .iter()
+                .take_while(|byte| byte.is_ascii_whitespace())
+                .count()
+    };
+
+    let mut index = 0;
+    while let Some(pos) = find(&ascii_bytes[index..], name) {
+        let offset = index + pos;
+        index = skip_whitespace(offset + name.len());
+        if ascii_bytes.get(index) == Some(&b'=') {
+            return Some((offset, skip_whitespace(index + 1)));
+        }
+    }
+
+    None
+}
+
+fn search(name: &'static str, ascii_bytes: &[u8], quotes: &[u8]) -> Option<Declared> {
+    // Work on the bytes rather than a lossily converted string so that offsets can't land inside
+    // a replacement character
+    find_attribute_value(ascii_bytes, name.as_bytes())
+        .map(|(offset, start)| {
+            // Skip any opening quotes, remembering the one the value is quoted with
+            let rest = &ascii_bytes[start..];
+            let start = rest
+                .iter()
+                .position(|byte| !quotes.contains(byte))
+                .unwrap_or(rest.len());
+            let quote = start.checked_sub(1).map(|index| rest[index]);
+            let rest = &rest[start..];
+
+            // A quoted value ends at the matching quote, while an unquoted value, as in the HTML5
+            // <meta charset=utf-8>, ends at any quote, a ; that separates parameters, or the end of
+            // the processing instruction. Either ends at whitespace or the end of the tag should
+            // the value be malformed.
+            match rest.iter().position(|&byte| {
+                byte == b'>'
+                    || byte.is_ascii_whitespace()
+                    || match quote {
+                        Some(quote) => byte == quote,
+                        None => quotes.contains(&byte) || b"?/;".contains(&byte),
+                    }
+            }) {
+                Some(end) if quote.is_none() || quote == Some(rest[end]) => Declared {
+                    name,
+                    value: String::from_utf8_lossy(&rest[..end]).into_owned(),
+                    truncated: false,
+                    offset,
+                },
+                end => {
+                    // Make the best of it by taking what looks like a name up to the end of the tag,
+                    // or a mismatched quote
+                    let rest = &rest[..end.unwrap_or(rest.len())];
+                    let end = rest
+                        .iter()
+                        .position(|&byte| {
+                            byte.is_ascii_whitespace()
+                                || b"?/>".contains(&byte)
+                                || quotes.contains(&byte)
+                        })
+                        .unwrap_or(rest.len());
+                    Declared {
+                        name,
+                        value: String::from_utf8_lossy(&rest[..end]).into_owned(),
+                        truncated: true,
+                        offset,
+                    }
+                }
+            }
+        })
+        .filter(|declared| is_plausible_charset(&declared.value))
+}
+
+// Broken generators sometimes put a URL, such as a namespace, where the charset should be, or
+// leave the value empty. Charset names are never empty, never contain whitespace and never look
+// like the start of a URL.
+fn is_plausible_charset(value: &str) -> bool {
+    !(value.is_empty()
+        || value.contains(char::is_whitespace)
+        || value.contains("//")
+        || value.contains(":/")
+        || value.ends_with(':'))
+}
+
+// Blank out <?php ... ?> blocks so that strings in the code aren't mistaken for declarations. An
+// unterminated block runs to the end of the text.
+fn mask_php_blocks(ascii_bytes: &mut [u8]) {
+    let lowercase = ascii_bytes.to_ascii_lowercase();
+    let mut index = 0;
+    while let Some(pos) = find(&lowercase[index..], b"<?php") {
+        let start = index + pos;
+        let end = find(&lowercase[start..], b"?>").map_or(lowercase.len(), |pos| start + pos + 2);
+        for byte in &mut ascii_bytes[start..end] {
+            *byte = b' ';
+        }
+        index = end;
+    }
+}
+
+// Blank out processing instructions with targets that start with xml, such as <?xml-stylesheet
+// ... ?>, so that their pseudo-attributes aren't mistaken for the XML declaration. Only <?xml
+// followed by whitespace is the declaration.
+fn mask_xml_processing_instructions(ascii_bytes: &mut [u8]) {
+    let mut index = 0;
+    while let Some(pos) = find(&ascii_bytes[index..], b"<?xml") {
+        let start = index + pos;
+        let is_declaration = match ascii_bytes.get(start + 5) {
+            Some(byte) => byte.is_ascii_whitespace() || *byte == b'?',
+            None => true,
+        };
+        if is_declaration {
+            index = start + 5;
+            continue;
+        }
+
+        let end =
+            find(&ascii_bytes[start..], b"?>").map_or(ascii_bytes.len(), |pos| start + pos + 2);
+        for byte in &mut ascii_bytes[start..end] {
+            *byte = b' ';
+        }
+        index = end;
+    }
+}
+
+// Find the charset in the Content-Type of the header entry of a gettext .po file, which looks
+// like:
+//
+//     msgid ""
+//     msgstr ""
+//     "Content-Type: text/plain; charset=UTF-8\n"
+fn search_po_header(bytes: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    // The header is the translation of the empty msgid, which must come first
+    if lines.next()? != "msgid \"\"" {
+        return None;
+    }
+    let first = lines.next()?.strip_prefix("msgstr")?.trim_start();
+
+    let mut header = String::new();
+    for line in Some(first).into_iter().chain(lines) {
+        match line
+            .strip_prefix('"')
+            .and_then(|line| line.strip_suffix('"'))
+        {
+            Some(string) => header.push_str(string),
+            None => break,
+        }
+    }
+
+    header
+        .split("\\n")
+        .filter_map(|field| {
+            let colon = field.find(':')?;
+            if field[..colon].trim().eq_ignore_ascii_case("content-type") {
+                charset_from_content_type(&field[colon + 1..])
+            } else {
+                None
+            }
+        })
+        .next()
+        // Templates have a placeholder rather than a charset
+        .filter(|charset| charset != "CHARSET")
+}
+
+// Find a Charset: or Encoding: field in the [Script Info] section of an Advanced SubStation Alpha
+// file, which looks like:
+//
+//     [Script Info]
+//     ; Comment
+//     Title: Example
+//     Charset: windows-1251
+fn search_ass_charset(ascii_bytes: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(ascii_bytes);
+    let mut in_script_info = false;
+
+    for line in text.lines() {
+        // The first line may start with what's left of a byte order mark
+        let line = line.trim_start_matches(|c: char| !c.is_ascii()).trim();
+        if line.starts_with('[') {
+            in_script_info = line.eq_ignore_ascii_case("[script info]");
+            continue;
+        }
+        if !in_script_info || line.starts_with(';') {
+            continue;
+        }
+
+        let colon = match line.find(':') {
+            Some(colon) => colon,
+            None => continue,
+        };
+        let name = line[..colon].trim();
+        let value = line[colon + 1..].trim();
+        if (name.eq_ignore_ascii_case("charset") || name.eq_ignore_ascii_case("encoding"))
+            && !value.is_empty()
+        {
+            return Some(value.to_string());
+        }
+    }
+
+    None
+}
+
+// The normalised charset of a CSS `@charset "name";` rule at the very start of the text. This is
+// far stricter than `search` as the CSS specification only recognises the rule byte for byte.
+fn search_css_charset(ascii_bytes: &[u8]) -> Option<String> {
+    let text = ascii_bytes
+        .strip_prefix(b"\xEF\xBB\xBF")
+        .unwrap_or(ascii_bytes);
+    let value = text.strip_prefix(b"@charset \"")?;
+    let len = value.iter().position(|&byte| byte == b'"')?;
+    if value.get(len + 1) != Some(&b';') {
+        return None;
+    }
+
+    let charset = normalise(core::str::from_utf8(&value[..len]).ok()?.trim());
+    match charset.as_str() {
+        "" => None,
+        "utf-16be" | "utf-16le" => Some("utf-8".to_string()),
+        _ => Some(charset),
+    }
+}
+
+// Find the code page declared by the \ansicpg control word of an RTF document, like:
+//
+//     {\rtf1\ansi\ansicpg1251\deff0
+fn search_rtf_code_page(ascii_bytes: &[u8]) -> Option<String> {
+    let start = find(ascii_bytes, b"\\ansicpg")? + 8;
+    let digits = ascii_bytes[start..]
+        .iter()
+        .take_while(|byte| byte.is_ascii_digit())
+        .count();
+    let code_page: u32 = core::str::from_utf8(&ascii_bytes[start..start + digits])
+        .ok()?
+        .parse()
+        .ok()?;
+    if code_page == 0 {
+        return None;
+    }
+
+    let charset = match code_page {
+        866 => "ibm866",
+        874 => "windows-874",
+        932 => "shift_jis",
+        936 => "gb18030",
+        949 => "euc-kr",
+        950 => "big5",
+        1250 => "windows-1250",
+        1251 => "windows-1251",
+        1252 => "windows-1252",
+        1253 => "windows-1253",
+        1254 => "windows-1254",
+        1255 => "windows-1255",
+        1256 => "windows-1256",
+        1257 => "windows-1257",
+        1258 => "windows-1258",
+        10000 => "macintosh",
+        10007 => "x-mac-cyrillic",
+        20866 => "koi8-r",
+        21866 => "koi8-u",
+        28591 => "iso-8859-1",
+        28592 => "iso-8859-2",
+        28593 => "iso-8859-3",
+        28594 => "iso-8859-4",
+        28595 => "iso-8859-5",
+        28596 => "iso-8859-6",
+        28597 => "iso-8859-7",
+        28598 => "iso-8859-8",
+        28599 => "iso-8859-9",
+        28603 => "iso-8859-13",
+        28605 => "iso-8859-15",
+        54936 => "gb18030",
+        65001 => "utf-8",
+        // Leave other code pages for the consumer to resolve
+        _ => return Some(format!("cp{}", code_page)),
+    };
+    Some(charset.to_string())
+}
+
+// Find the values of the CHARSET parameters of vCard properties, like:
+//
+//     N;CHARSET=ISO-8859-1;ENCODING=8BIT:Müller;Hans
+fn search_vcard_charsets(ascii_bytes: &[u8]) -> Vec<String> {
+    let lowercase = ascii_bytes.to_ascii_lowercase();
+    let needle = b";charset=";
+
+    let mut charsets = Vec::new();
+    let mut index = 0;
+    while let Some(pos) = find(&lowercase[index..], needle) {
+        let start = index + pos + needle.len();
+        let value: Vec<u8> = ascii_bytes[start..]
+            .iter()
+            .cloned()
+            .take_while(|&byte| !b":;,\r\n".contains(&byte))
+            .filter(|&byte| byte != b'"')
+            .collect();
+        if !value.is_empty() {
+            charsets.push(String::from_utf8_lossy(&value).into_owned());
+        }
+        index = start;
+    }
+
+    charsets
+}
+
+// Determine if decoding numeric character references in the text reveals a charset or encoding
+// declaration that isn't otherwise present
+fn has_obfuscated_declaration(haystack: &[u8], descriptor: Option<&Descriptor>) -> bool {
+    let ascii_bytes = ascii_bytes(haystack, descriptor);
+    if find(&ascii_bytes, b"&#").is_none() {
+        return false;
+    }
+
+    let raw = ascii_bytes.to_ascii_lowercase();
+    let decoded = decode_numeric_character_references(&raw);
+    [&b"charset"[..], &b"encoding"[..]]
+        .iter()
+        .any(|needle| count(&decoded, needle) > count(&raw, needle))
+}
+
+// Decode character references like &#99; and &#x63; that refer to ASCII characters
+fn decode_numeric_character_references(text: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(text.len());
+    let mut index = 0;
+    while index < text.len() {
+        if text[index..].starts_with(b"&#") {
+            let (digits, radix) = if text.get(index + 2) == Some(&b'x') {
+                (index + 3, 16)
+            } else {
+                (index + 2, 10)
+            };
+            let end = text[digits..]
+                .iter()
+                .position(|&byte| byte == b';')
+                .map(|position| digits + position);
+            let value = end.and_then(|end| {
+                core::str::from_utf8(&text[digits..end])
+                    .ok()
+                    .and_then(|digits| u8::from_str_radix(digits, radix).ok())
+                    .filter(u8::is_ascii)
+            });
+            if let (Some(end), Some(value)) = (end, value) {
+                decoded.push(value.to_ascii_lowercase());
+                index = end + 1;
+                continue;
+            }
+        }
+
+        decoded.push(text[index]);
+        index += 1;
+    }
+
+    decoded
+}
+
+fn count(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .filter(|window| *window == needle)
+        .count()
+}
+
+fn search_charset_processing_instruction(
+    haystack: &[u8],
+    descriptor: Option<&Descriptor>,
+) -> Option<String> {
+    let ascii_bytes = ascii_bytes(haystack, descriptor);
+    let needle = b"<?charset";
+
+    let start = find(&ascii_bytes, needle)? + needle.len();
+    // The target must be exactly "charset", followed by whitespace
+    match ascii_bytes.get(start) {
+        Some(byte) if byte.is_ascii_whitespace() => {}
+        _ => return None,
+    }
+
+    let value: Vec<u8> = ascii_bytes[start..]
+        .iter()
+        .cloned()
+        .skip_while(u8::is_ascii_whitespace)
+        .take_while(|byte| !byte.is_ascii_whitespace() && *byte != b'?')
+        .collect();
+    if value.is_empty() {
+        None
+    } else {
+        Some(String::from_utf8_lossy(&value).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use std::io::Cursor;
+
+    // Encode text as UTF-16LE with a byte order mark
+    pub(crate) fn utf16le(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&[unit as u8, (unit >> 8) as u8]);
+        }
+        bytes
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_empty() {
+        let mut text_cursor = Cursor::new("");
+        let detected_charsets = detect(&mut text_cursor, None).unwrap();
+        assert!(detected_charsets.is_empty());
+        assert!(detect_slice(b"", None).is_empty());
+        assert!(detect_bytes(b"", None).unwrap().is_empty());
+        let (detected_charsets, _) = detect_timed(&mut Cursor::new(""), None).unwrap();
+        assert!(detected_charsets.is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_with_str_hint() {
+        let text = b"<p>Hello</p>";
+        let header = String::from("utf-16be, iso-8859-1;q=0.5");
+        assert_eq!(
+            detect_with_str_hint(&mut Cursor::new(text), Some(&header)).unwrap(),
+            detect(&mut Cursor::new(text), Some(header.clone())).unwrap()
+        );
+        assert_eq!(
+            detect_with_str_hint(&mut Cursor::new(text), None).unwrap(),
+            vec!["utf-8".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_short() {
+        let error = detect(&mut Cursor::new("<p"), None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        let error = detect_timed(&mut Cursor::new("<p"), None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_nul_bytes() {
+        let text = [0u8; 16];
+        assert!(detect(&mut Cursor::new(&text), None).unwrap().is_empty());
+        assert!(detect_slice(&text[..4], None).is_empty());
+        assert_eq!(
+            detect_slice(&text, Some("iso-8859-1".to_string())),
+            vec!["iso-8859-1".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_4_bytes() {
+        let mut text_cursor = Cursor::new("1234");
+        let detected_charsets = detect(&mut text_cursor, None).unwrap();
+        assert!(detected_charsets.is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_detailed_empty() {
+        let mut text_cursor = Cursor::new("");
+        let result = detect_detailed(&mut text_cursor, None).unwrap();
+        assert_eq!(
+            result.detections,
+            vec![Detection {
+                charset: "utf-8".to_string(),
+                source: DetectionSource::EmptyInput,
+            }]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_detailed_short_utf8() {
+        let mut text_cursor = Cursor::new("ab");
+        let result = detect_detailed(&mut text_cursor, None).unwrap();
+        assert_eq!(
+            result.detections,
+            vec![Detection {
+                charset: "utf-8".to_string(),
+                source: DetectionSource::Utf8Fallback,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_detect_slice() {
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel></channel>";
+        assert_eq!(detect_slice(text, None), vec!["iso-8859-1".to_string()]);
+    }
+
+    #[test]
+    fn test_detect_slice_non_utf8_value() {
+        // 16-bit little endian declaration whose value is made up of invalid UTF-8
+        let mut text = b"<\x00?\x00".to_vec();
+        for &byte in b"encoding=\"\xFF\xC3\xE2\xFE" {
+            text.push(byte);
+            text.push(0);
+        }
+        let _ = detect_slice(&text, None);
+    }
+
+    #[test]
+    fn test_search_invalid_utf8_around_needle() {
+        let text = b"\xC3encoding=\"utf-8\"\xC3\x80\xFF";
+        let declared = search("encoding", text, QUOTES).unwrap();
+        assert_eq!(declared.value, "utf-8");
+        assert!(!declared.truncated);
+    }
+
+    #[test]
+    fn test_truncated_declaration() {
+        let text = b"<?xml version=\"1.0\" encoding=\"UTF-8";
+        let result = detect_core(text, None, &Options::default());
+        assert_eq!(result.charsets(), vec!["utf-8".to_string()]);
+        assert_eq!(
+            result.warnings,
+            vec![DetectionWarning::TruncatedDeclaration]
+        );
+    }
+
+    #[test]
+    fn test_declaration_missing_closing_quote() {
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1?><rss version=\"2.0\"></rss>";
+        let result = detect_core(text, None, &Options::default());
+        assert_eq!(result.charsets(), vec!["iso-8859-1".to_string()]);
+        assert_eq!(
+            result.warnings,
+            vec![DetectionWarning::TruncatedDeclaration]
+        );
+    }
+
+    #[test]
+    fn test_declaration_mismatched_quotes() {
+        let cases: &[&[u8]] = &[
+            b"<?xml version=\"1.0\" encoding=\"utf-8'?><rss version='2.0'></rss>",
+            b"<?xml version=\"1.0\" encoding='utf-8\"?><rss version=\"2.0\"></rss>",
+            b"<meta charset=\"utf-8'><title>Example</title>",
+            b"<?xml version=\"1.0\" encoding=\"utf-8",
+            b"<?xml version=\"1.0\" encoding=\"utf-8\n<rss></rss>",
+        ];
+        for &text in cases {
+            let result = detect_core(text, Some("iso-8859-1"), &Options::default());
+            assert_eq!(
+                result.charsets(),
+                vec!["utf-8".to_string(), "iso-8859-1".to_string()],
+                "{}",
+                String::from_utf8_lossy(text)
+            );
+            assert_eq!(
+                result.warnings,
+                vec![DetectionWarning::TruncatedDeclaration]
+            );
+        }
+
+        // Matching quotes are fine, whichever is used
+        for &text in &[&b"<meta charset='utf-8'>"[..], b"<meta charset=\"utf-8\">"] {
+            let declared = search("charset", text, QUOTES).unwrap();
+            assert_eq!(declared.value, "utf-8");
+            assert!(!declared.truncated);
+        }
+    }
+
+    #[test]
+    fn test_empty_declaration() {
+        let cases: &[&[u8]] = &[
+            b"<?xml version=\"1.0\" encoding=\"\"?><rss version=\"2.0\"></rss>",
+            b"<meta charset=''><title>Example</title>",
+            b"<meta charset=\" \"><title>Example</title>",
+        ];
+        for &text in cases {
+            let result = detect_core(text, None, &Options::default());
+            assert_eq!(
+                result.charsets(),
+                vec!["utf-8".to_string()],
+                "{}",
+                String::from_utf8_lossy(text)
+            );
+            assert!(result.warnings.is_empty());
+            assert_eq!(detect_slice(text, None), vec!["utf-8".to_string()]);
+        }
+
+        // A later declaration is used instead
+        let text = b"<?xml version=\"1.0\" encoding=\"\"?><meta charset=\"koi8-r\">";
+        assert_eq!(detect_slice(text, None), vec!["koi8-r".to_string()]);
+    }
+
+    #[test]
+    fn test_same_encoding() {
+        let declared = b"<?xml version=\"1.0\" encoding=\"windows-1252\"?><p>caf\xE9</p>";
+        let hinted_elsewhere = b"<meta charset=\"Windows-1252\"><p>na\xEFve</p>";
+        assert!(same_encoding(declared, hinted_elsewhere));
+        assert!(same_encoding(b"\xFF\xFE<\x00p\x00>\x00", b"\xFF\xFEa\x00"));
+        assert!(same_encoding(b"", b"<p>Hello</p>"));
+
+        // Neither can be determined
+        assert!(same_encoding(b"caf\xE9", b"na\xEFve"));
+    }
+
+    #[test]
+    fn test_not_same_encoding() {
+        assert!(!same_encoding(
+            b"\xFF\xFE<\x00p\x00>\x00",
+            b"\xFE\xFF\x00<\x00p\x00>"
+        ));
+        assert!(!same_encoding(
+            b"<meta charset=\"iso-8859-2\">",
+            b"<meta charset=\"iso-8859-15\">"
+        ));
+        assert!(!same_encoding(b"caf\xE9", b"<p>Hello</p>"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_repeated_byte_windows() {
+        let all_options = [
+            Options::default(),
+            Options {
+                lenient: true,
+                strict: true,
+                ..Options::default()
+            },
+        ];
+        let cases: &[(u8, &[&str])] = &[(0x00, &[]), (0x3C, &["utf-8"]), (0xFF, &[])];
+
+        for &(byte, expected) in cases {
+            let text = vec![byte; PREFIX_LEN * 2];
+            let expected: Vec<String> = expected.iter().map(|name| name.to_string()).collect();
+
+            assert_eq!(detect_slice(&text, None), expected, "{:#04X}", byte);
+            assert_eq!(detect(&mut Cursor::new(&text), None).unwrap(), expected);
+            for options in &all_options {
+                assert_eq!(
+                    detect_detailed_with_options(&mut Cursor::new(&text), None, options)
+                        .unwrap()
+                        .charsets(),
+                    expected,
+                    "{:#04X} with {:?}",
+                    byte,
+                    options
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_repeated_wide_patterns() {
+        // Each repeats a four byte pattern that is recognised without a BOM
+        let patterns: &[&[u8]] = &[
+            b"\x00\x00\x00\x3C",
+            b"\x3C\x00\x00\x00",
+            b"\x00\x00\x3C\x00",
+            b"\x00\x3C\x00\x00",
+            b"\x00\x3C\x00\x3F",
+            b"\x3C\x00\x3F\x00",
+        ];
+
+        for pattern in patterns {
+            let text: Vec<u8> = pattern.iter().cycle().take(PREFIX_LEN).cloned().collect();
+            assert_eq!(detect_slice(&text, None), vec!["utf-8".to_string()]);
+            // The byte order is resolved, or both are reported
+            let resolved = detect_slice(&text, Some("utf-16".to_string()));
+            assert!(!resolved.is_empty() && resolved.len() <= 2, "{:?}", pattern);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_utf7_signature() {
+        for &signature in &[b"+/v8", b"+/v9", b"+/v+", b"+/v/"] {
+            let mut text = signature.to_vec();
+            text.extend_from_slice(b"-+ADw-p+AD4-Hello+ADw-/p+AD4-");
+            assert_eq!(
+                detect(&mut Cursor::new(&text), None).unwrap(),
+                vec!["utf-7".to_string()]
+            );
+            assert_eq!(
+                detect_detailed(&mut Cursor::new(&text), None)
+                    .unwrap()
+                    .detections[0]
+                    .source,
+                DetectionSource::ByteOrderMark
+            );
+        }
+
+        // Not a signature
+        assert_eq!(detect_slice(b"+/v-abc", None), vec!["utf-8".to_string()]);
+    }
+
+    #[test]
+    fn test_bom_length() {
+        let cases: &[(&[u8], u8, usize)] = &[
+            (b"\xEF\xBB\xBF<", 8, 3),
+            (b"\xFE\xFF\x00<", 16, 2),
+            (b"\xFF\xFE<\x00", 16, 2),
+            (b"\x00\x00\xFE\xFF", 32, 4),
+            (b"\xFF\xFE\x00\x00", 32, 4),
+            (b"\x00\x00\xFF\xFE", 32, 4),
+            (b"\xFE\xFF\x00\x00", 32, 4),
+            (b"<?xm", 8, 0),
+            (b"\x00<\x00?", 16, 0),
+            (b"<\x00\x00\x00", 32, 0),
+            (b"\x4C\x6F\xA7\x94", 8, 0),
+        ];
+
+        for &(bytes, width, length) in cases {
+            let info = detect_structure(bytes).unwrap();
+            assert_eq!(info.width(), width, "{:?}", bytes);
+            assert_eq!(bom_length(&info), length, "{:?}", bytes);
+            assert_eq!(info.has_bom(), length > 0);
+        }
+
+        let info = detect_structure(b"+/v8").unwrap();
+        assert_eq!(bom_length(&info), 0);
+        assert!(info.has_bom());
+
+        assert_eq!(detect_structure(b"<p>Hello</p>"), None);
+        assert_eq!(detect_structure(b"\xEF\xBB"), None);
+    }
+
+    #[test]
+    fn test_unquoted_xml_declaration() {
+        let text = b"<?xml version=1.0 encoding=UTF-8?><rss version=\"2.0\"></rss>";
+        assert_eq!(detect_slice(text, None), vec!["utf-8".to_string()]);
+
+        let text = b"<?xml version=1.0 encoding=ISO-8859-1 ?><rss version=\"2.0\"></rss>";
+        assert_eq!(detect_slice(text, None), vec!["iso-8859-1".to_string()]);
+
+        let declared = search("charset", b"content=text/html;charset=koi8-r;foo", QUOTES).unwrap();
+        assert_eq!(declared.value, "koi8-r");
+        assert!(!declared.truncated);
+    }
+
+    #[test]
+    fn test_unquoted_html5_meta_charset() {
+        for text in &[
+            &b"<meta charset=windows-1252><link rel=\"stylesheet\" href=\"a.css\">"[..],
+            b"<meta charset=windows-1252 ><p class=\"a\">",
+            b"<meta charset=windows-1252/><p class=\"a\">",
+        ] {
+            let declared = search("charset", text, QUOTES).unwrap();
+            assert_eq!(declared.value, "windows-1252");
+            assert!(!declared.truncated);
+        }
+
+        let text = b"<?xml version=\"1.0\" encoding=windows-1252?><p class=\"a\">";
+        assert_eq!(
+            search("encoding", text, QUOTES).unwrap().value,
+            "windows-1252"
+        );
+
+        // The http-equiv form is unaffected
+        let declared = search(
+            "charset",
+            b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=Shift-JIS\">",
+            QUOTES,
+        )
+        .unwrap();
+        assert_eq!(declared.value, "Shift-JIS");
+        assert!(!declared.truncated);
+    }
+
+    #[test]
+    fn test_whitespace_around_equals() {
+        let text = b"<?xml version = \"1.0\" encoding =\n\t'ISO-8859-1'?>";
+        assert_eq!(
+            search("encoding", text, QUOTES).unwrap().value,
+            "ISO-8859-1"
+        );
+
+        let text = b"<meta charset= windows-1252><p>";
+        assert_eq!(
+            search("charset", text, QUOTES).unwrap().value,
+            "windows-1252"
+        );
+
+        // A mention of the name that isn't an attribute is skipped
+        let text = b"<!-- charset detection --><meta charset =\"koi8-r\">";
+        assert_eq!(search("charset", text, QUOTES).unwrap().value, "koi8-r");
+        assert!(search("charset", b"<p>charset</p>", QUOTES).is_none());
+    }
+
+    #[test]
+    fn test_backtick_delimited_declaration() {
+        let text = b"<meta http-equiv=\"Content-Type\" content=`text/html; charset=`utf-8``>";
+        let mut options = Options {
+            strict: true,
+            ..Options::default()
+        };
+        assert_ne!(
+            detect_core(text, None, &options).charsets(),
+            vec!["utf-8".to_string()]
+        );
+
+        options.lenient = true;
+        let result = detect_core(text, None, &options);
+        assert_eq!(result.charsets(), vec!["utf-8".to_string()]);
+        assert_eq!(result.detections[0].source, DetectionSource::Declaration);
+    }
+
+    #[test]
+    fn test_margin_conflicting() {
+        // The BOM says UTF-16 but the declaration disagrees
+        let text = utf16le("<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>Hello</p>");
+        let result = detect_core(&text, None, &Options::default());
+        assert_eq!(result.margin(), Some(20));
+
+        // Neither byte order is favoured
+        let result = detect_core(b"abcd", Some("utf-16"), &Options::default());
+        assert_eq!(result.margin(), Some(0));
+    }
+
+    #[test]
+    fn test_first_declaration_wins() {
+        let cases: &[(&[u8], &str)] = &[
+            (
+                b"<meta charset=\"windows-1251\"><svg encoding=\"iso-8859-1\"></svg>",
+                "windows-1251",
+            ),
+            (
+                b"<?xml version=\"1.0\" encoding=\"iso-8859-2\"?><meta charset=\"utf-8\">",
+                "iso-8859-2",
+            ),
+            (
+                b"<meta charset=\"koi8-r\"><meta http-equiv=\"Content-Type\" \
+                  content=\"text/html; charset=windows-1251\">",
+                "koi8-r",
+            ),
+        ];
+        for &(text, expected) in cases {
+            assert_eq!(
+                detect_core(text, None, &Options::default()).charsets(),
+                vec![expected.to_string()]
+            );
+        }
+    }
+
+    #[test]
+    fn test_detect_bom() {
+        let cases: &[(&[u8], BomKind, usize, Option<&str>)] = &[
+            (b"\xEF\xBB\xBF<", BomKind::Utf8, 3, Some("utf-8")),
+            (b"\xFF\xFE<\x00", BomKind::Utf16Le, 2, Some("utf-16le")),
+            (b"\xFE\xFF\x00<", BomKind::Utf16Be, 2, Some("utf-16be")),
+            (b"\xFF\xFE\x00\x00", BomKind::Ucs4Le, 4, Some("ucs-4le")),
+            (b"\x00\x00\xFE\xFF", BomKind::Ucs4Be, 4, Some("ucs-4be")),
+            (b"\x00\x00\xFF\xFE", BomKind::Ucs4Unusual2143, 4, None),
+            (b"\xFE\xFF\x00\x00", BomKind::Ucs4Unusual3412, 4, None),
+            (b"+/v8", BomKind::Utf7, 0, Some("utf-7")),
+            (b"+/v9", BomKind::Utf7, 0, Some("utf-7")),
+            (b"+/v+", BomKind::Utf7, 0, Some("utf-7")),
+            (b"+/v/", BomKind::Utf7, 0, Some("utf-7")),
+        ];
+        for &(bytes, kind, length, charset) in cases {
+            assert_eq!(detect_bom(bytes), Some(kind), "{:?}", bytes);
+            assert_eq!(kind.length(), length, "{:?}", kind);
+            assert_eq!(kind.charset(), charset, "{:?}", kind);
+            assert_eq!(
+                detect_structure(bytes).map(|info| bom_length(&info)),
+                Some(length)
+            );
+        }
+
+        // Patterns without a byte order mark
+        for &bytes in &[
+            &b"<?xm"[..],
+            b"\x00<\x00?",
+            b"\x4C\x6F\xA7\x94",
+            b"+/v-",
+            b"\xEF\xBB\xBF",
+            b"",
+        ] {
+            assert_eq!(detect_bom(bytes), None, "{:?}", bytes);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_utf8_bom_is_authoritative() {
+        let text = b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>caf\xC3\xA9</p>";
+        for options in &[
+            Options::default(),
+            Options {
+                strict: true,
+                ..Options::default()
+            },
+        ] {
+            let result = detect_core(text, None, options);
+            assert_eq!(result.charsets(), vec!["utf-8".to_string()]);
+            assert_eq!(
+                result.warnings,
+                vec![DetectionWarning::ConflictingByteOrderMark]
+            );
+            // The offset accounts for the BOM
+            assert_eq!(result.declaration_offset, Some(23));
+        }
+        assert!(try_detect(&mut Cursor::new(&text[..]), None).is_ok());
+
+        // An agreeing declaration is fine
+        let text = b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"utf-8\"?><p>Hello</p>";
+        let result = detect_core(text, None, &Options::default());
+        assert_eq!(result.charsets(), vec!["utf-8".to_string()]);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_conflicting_bom() {
+        let options = Options {
+            strict: true,
+            ..Options::default()
+        };
+
+        for &declared in &["utf-8", "iso-8859-1", "ucs-4le"] {
+            let text = utf16le(&format!(
+                "<?xml version=\"1.0\" encoding=\"{}\"?>",
+                declared
+            ));
+            let result = detect_core(&text, None, &options);
+            assert!(
+                result
+                    .warnings
+                    .contains(&DetectionWarning::ConflictingByteOrderMark),
+                "{}",
+                declared
+            );
+            let error = detect_strict(&mut Cursor::new(&text), None).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+            // Only checked in strict mode
+            let result = detect_core(&text, None, &Options::default());
+            assert!(result.warnings.is_empty());
+        }
+
+        for &declared in &["utf-16", "UTF-16LE", "ucs-2"] {
+            let text = utf16le(&format!(
+                "<?xml version=\"1.0\" encoding=\"{}\"?>",
+                declared
+            ));
+            assert!(detect_core(&text, None, &options).warnings.is_empty());
+            assert!(detect_strict(&mut Cursor::new(&text), None)
+                .unwrap()
+                .contains(&"utf-16le".to_string()));
+        }
+
+        let text = b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>";
+        assert!(detect_strict(&mut Cursor::new(&text[..]), None).is_err());
+        let text = b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"UTF-8\"?>";
+        assert_eq!(
+            detect_strict(&mut Cursor::new(&text[..]), None).unwrap(),
+            vec!["utf-8".to_string()]
+        );
+        // No BOM to conflict with
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>";
+        assert_eq!(
+            detect_strict(&mut Cursor::new(&text[..]), None).unwrap(),
+            vec!["iso-8859-1".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_margin_clean() {
+        // A generic default from the server is easily outweighed by the BOM
+        let text = b"\xFF\xFE<\x00p\x00>\x00";
+        let result = detect_core(text, Some("windows-1252"), &Options::default());
+        assert_eq!(result.margin(), Some(60));
+
+        let result = detect_core(text, None, &Options::default());
+        assert_eq!(result.margin(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_bom_only() {
+        let mut text_cursor = Cursor::new(b"\xFE\xFF\x00<\x00?".to_vec());
+        assert_eq!(
+            detect_bom_only(&mut text_cursor).unwrap(),
+            Some("utf-16be".to_string())
+        );
+        assert_eq!(text_cursor.position(), 4);
+
+        let mut text_cursor = Cursor::new("<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>");
+        assert_eq!(detect_bom_only(&mut text_cursor).unwrap(), None);
+
+        let mut text_cursor = Cursor::new("");
+        assert_eq!(detect_bom_only(&mut text_cursor).unwrap(), None);
+    }
+
+    #[test]
+    fn test_charset_processing_instruction() {
+        let text = b"<?charset iso-8859-15?><price>\xA4 10</price>";
+        assert!(detect_core(text, None, &Options::default())
+            .detections
+            .is_empty());
+
+        let options = Options {
+            lenient: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(text, None, &options).detections,
+            vec![Detection {
+                charset: "iso-8859-15".to_string(),
+                source: DetectionSource::ProcessingInstruction,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_charset_processing_instruction_lower_priority() {
+        let text = b"<?xml version=\"1.0\" encoding=\"utf-8\"?><?charset iso-8859-15?>";
+        let options = Options {
+            lenient: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["utf-8".to_string(), "iso-8859-15".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_with_peeked_bom() {
+        let text = b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00";
+        let mut text_cursor = Cursor::new(text.to_vec());
+
+        // Peek at the first four bytes as a format sniffing step might
+        let mut peeked = [0u8; 4];
+        text_cursor.read_exact(&mut peeked).unwrap();
+        assert_eq!(&peeked, b"\xFF\xFE<\x00");
+
+        let detected_charsets = detect_with_peeked_bom(peeked, &mut text_cursor, None).unwrap();
+        assert_eq!(detected_charsets, vec!["utf-16le".to_string()]);
+    }
+
+    #[test]
+    fn test_multibyte_utf8_outweighs_declaration() {
+        let text = "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\
+            <p>Crème brûlée, naïve café, smörgåsbord, jalapeño, Æsir, Øresund</p>";
+        let result = detect_core(text.as_bytes(), None, &Options::default());
+        assert_eq!(
+            result.detections,
+            vec![
+                Detection {
+                    charset: "utf-8".to_string(),
+                    source: DetectionSource::Utf8Multibyte,
+                },
+                Detection {
+                    charset: "iso-8859-1".to_string(),
+                    source: DetectionSource::Declaration,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multibyte_utf8_doesnt_outweigh_multibyte_declaration() {
+        let text = "<?xml version=\"1.0\" encoding=\"Shift_JIS\"?><p>Crème brûlée</p>";
+        assert_eq!(
+            detect_core(text.as_bytes(), None, &Options::default()).charsets(),
+            vec!["shift_jis".to_string()]
+        );
+
+        // Plain ASCII is no evidence of UTF-8
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>Creme brulee</p>";
+        assert_eq!(
+            detect_core(text, None, &Options::default()).charsets(),
+            vec!["iso-8859-1".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_leading_junk_lenient() {
+        let text = b"\xFF\xFE<?xml version=\"1.0\" encoding=\"shift_jis\"?><rss></rss>";
+        assert_eq!(
+            detect_core(text, None, &Options::default()).charsets(),
+            vec!["utf-16le".to_string()]
+        );
+
+        let options = Options {
+            lenient: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["shift_jis".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_leading_junk_before_bom_lenient() {
+        let mut text = b"\x00\x00\xFF\xFE".to_vec();
+        for &byte in b"<?xml version=\"1.0\" encoding=\"utf-16\"?>" {
+            text.extend_from_slice(&[byte, 0]);
+        }
+
+        let options = Options {
+            lenient: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(&text, None, &options).charsets(),
+            vec!["utf-16le".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_grouped() {
+        let text = utf16le("<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>");
+        let grouped =
+            detect_grouped(&mut Cursor::new(&text), Some("windows-1252".to_string())).unwrap();
+
+        let mut expected = BTreeMap::new();
+        expected.insert(DetectionSource::Declaration, vec!["iso-8859-1".to_string()]);
+        expected.insert(DetectionSource::Hint, vec!["windows-1252".to_string()]);
+        expected.insert(DetectionSource::ByteOrderMark, vec!["utf-16le".to_string()]);
+        assert_eq!(grouped, expected);
+    }
+
+    #[test]
+    fn test_is_ebcdic() {
+        assert!(is_ebcdic("cp037"));
+        assert!(is_ebcdic("ibm-1047"));
+        assert!(is_ebcdic("ibm500"));
+        assert!(is_ebcdic("ebcdic-cp-us"));
+        assert!(!is_ebcdic("cp1252"));
+        assert!(!is_ebcdic("ibm866"));
+        assert!(!is_ebcdic("utf-8"));
+    }
+
+    #[test]
+    fn test_ebcdic_hint() {
+        // CUSTOMER 00042 SMITH
+        let mut record =
+            b"\xC3\xE4\xE2\xE3\xD6\xD4\xC5\xD9\x40\xF0\xF0\xF0\xF4\xF2\x40\xE2\xD4\xC9\xE3\xC8"
+                .to_vec();
+        // A binary field that happens to look like an ASCII declaration
+        record.extend_from_slice(b"charset=\"utf-8\"");
+        assert_eq!(
+            detect_slice(&record, Some("cp037".to_string())),
+            vec!["cp037".to_string()]
+        );
+
+        // EBCDIC spaces are valid UTF-8
+        let record = [0x40; 80];
+        assert_eq!(
+            detect_slice(&record, Some("IBM037".to_string())),
+            vec!["ibm037".to_string()]
+        );
+        assert_eq!(detect_slice(&record, None), vec!["utf-8".to_string()]);
+    }
+
+    #[test]
+    fn test_normalise() {
+        assert_eq!(normalise("UTF8"), "utf-8");
+        assert_eq!(normalise(".utf-8"), "utf-8");
+        assert_eq!(normalise("cp:1252"), "windows-1252");
+        assert_eq!(normalise("Windows:1251"), "windows-1251");
+        assert_eq!(normalise("windows-1252"), "windows-1252");
+        assert_eq!(normalise("cp1252"), "windows-1252");
+        assert_eq!(normalise("cp:437"), "cp:437");
+        assert_eq!(normalise("iso-8859-1"), "iso-8859-1");
+        assert_eq!(normalise("x-unknown"), "x-unknown");
+    }
+
+    #[test]
+    fn test_normalise_aliases() {
+        let aliases = [
+            ("latin1", "iso-8859-1"),
+            ("ISO_8859-1:1987", "iso-8859-1"),
+            ("csISOLatin1", "iso-8859-1"),
+            ("x-cp1252", "windows-1252"),
+            ("US-ASCII", "ascii"),
+            ("unicode-1-1-utf-8", "utf-8"),
+            ("UCS-2", "utf-16"),
+            ("unicodeFFFE", "utf-16be"),
+            ("ISO8859-15", "iso-8859-15"),
+            ("koi8_r", "koi8-r"),
+            ("x-sjis", "shift_jis"),
+            ("EUC-KR", "euc-kr"),
+            ("ks_c_5601-1987", "euc-kr"),
+            ("GB_2312-80", "gb18030"),
+            ("tis-620", "windows-874"),
+        ];
+        for &(alias, canonical) in &aliases {
+            assert_eq!(normalise(alias), canonical, "{}", alias);
+        }
+    }
+
+    #[test]
+    fn test_sloppy_hint() {
+        assert_eq!(
+            detect_slice(b"<p>caf\xE9</p>", Some("cp:1252".to_string())),
+            vec!["windows-1252".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_hint() {
+        assert_eq!(parse_hint("UTF8"), vec!["utf-8".to_string()]);
+        assert_eq!(
+            parse_hint("iso-8859-1;q=0.5, utf-8, *;q=0.1, windows-1252;Q=0.5, ascii;q=0"),
+            vec![
+                "utf-8".to_string(),
+                "iso-8859-1".to_string(),
+                "windows-1252".to_string(),
+            ]
+        );
+        assert!(parse_hint(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_hint_list() {
+        let hint = Some("iso-8859-1;q=0.5, shift_jis;q=0.8, utf-8");
+        let result = detect_core(b"<p>Hello</p>", hint, &Options::default());
+        assert_eq!(
+            result.charsets(),
+            vec![
+                "utf-8".to_string(),
+                "shift_jis".to_string(),
+                "iso-8859-1".to_string(),
+            ]
+        );
+        assert!(result
+            .detections
+            .iter()
+            .all(|detection| detection.source == DetectionSource::Hint));
+    }
+
+    #[test]
+    fn test_emit_bare_unicode_widths() {
+        let text = b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00";
+        let mut options = Options::default();
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["utf-16le".to_string()]
+        );
+
+        options.emit_bare_unicode_widths = true;
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["utf-16".to_string()]
+        );
+        assert_eq!(
+            detect_core(b"<p>Hello</p>", Some("UTF-16"), &options).charsets(),
+            vec!["utf-16".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_chained_readers() {
+        // The BOM is in the first reader and the declaration straddles the boundary
+        let text =
+            utf16le("<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><rss version=\"2.0\"></rss>");
+        let (first, second) = text.split_at(50);
+        let mut reader = first.chain(second);
+
+        let result = detect_detailed(&mut reader, None).unwrap();
+        assert_eq!(
+            result.detections,
+            vec![
+                Detection {
+                    charset: "iso-8859-1".to_string(),
+                    source: DetectionSource::Declaration,
+                },
+                Detection {
+                    charset: "utf-16le".to_string(),
+                    source: DetectionSource::ByteOrderMark,
+                },
+            ]
+        );
+
+        let mut reader = first.chain(second);
+        assert_eq!(
+            detect(&mut reader, None).unwrap(),
+            vec!["iso-8859-1".to_string(), "utf-16le".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_timed() {
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel></channel>";
+        let (detected_charsets, duration) =
+            detect_timed(&mut Cursor::new(&text[..]), None).unwrap();
+        assert_eq!(
+            detected_charsets,
+            detect(&mut Cursor::new(&text[..]), None).unwrap()
+        );
+        assert!(duration >= Duration::from_secs(0));
+    }
 
     #[test]
-    fn test_detect_empty() {
-        let mut text_cursor = Cursor::new("");
-        let detected_charsets = detect(&mut text_cursor, None);
-        assert!(detected_charsets.is_err()); // UnexpectedEof
+    fn test_obfuscated_declaration() {
+        let text = b"<meta http-equiv=\"Content-Type\" content=\"text/html; &#x63;harset=utf-7\">";
+        let options = Options {
+            strict: true,
+            ..Options::default()
+        };
+        let result = detect_core(text, None, &options);
+        assert_eq!(
+            result.warnings,
+            vec![DetectionWarning::SuspiciousDeclaration]
+        );
+        assert_eq!(result.charsets(), vec!["utf-8".to_string()]);
+
+        let result = detect_core(text, None, &Options::default());
+        assert!(result.warnings.is_empty());
     }
 
     #[test]
-    fn test_detect_4_bytes() {
-        let mut text_cursor = Cursor::new("1234");
-        let detected_charsets = detect(&mut text_cursor, None).unwrap();
-        assert!(detected_charsets.is_empty());
+    fn test_obfuscated_declaration_decimal() {
+        let text = b"<meta &#99;&#104;arset=\"utf-7\">";
+        assert!(has_obfuscated_declaration(text, None));
+    }
+
+    #[test]
+    fn test_unobfuscated_declaration_with_references() {
+        let text = b"<?xml version=\"1.0\" encoding=\"utf-8\"?><p>&#233;t&#xE9;</p>";
+        assert!(!has_obfuscated_declaration(text, None));
+    }
+
+    #[test]
+    fn test_office_xml_ignores_content() {
+        let text = b"<office:document-content><text:p>charset=\"iso-8859-1\"</text:p></office:document-content>";
+        let options = Options {
+            content_kind: ContentKind::OfficeXml,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(text, None, &options).detections,
+            vec![Detection {
+                charset: "utf-8".to_string(),
+                source: DetectionSource::Format,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_office_xml_invalid_utf8() {
+        let text = b"<office:document-content><text:p>caf\xE9</text:p></office:document-content>";
+        let options = Options {
+            content_kind: ContentKind::OfficeXml,
+            ..Options::default()
+        };
+        assert!(detect_core(text, None, &options).detections.is_empty());
+    }
+
+    #[test]
+    fn test_mask_xml_processing_instructions() {
+        let mut text =
+            b"<?xml-stylesheet href=\"a.css\"?><?xml version=\"1.0\"?><?xml-model?>".to_vec();
+        mask_xml_processing_instructions(&mut text);
+        let expected = [&[b' '; 31][..], b"<?xml version=\"1.0\"?>", &[b' '; 13][..]].concat();
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn test_stylesheet_before_declaration() {
+        let text = b"<?xml-stylesheet type=\"text/css\" href=\"style.css\" charset=\"utf-8\"?>\
+            <?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><html></html>";
+        assert_eq!(detect_slice(text, None), vec!["iso-8859-1".to_string()]);
+
+        // The charset of the stylesheet says nothing about the document
+        let text = b"<?xml version=\"1.0\"?>\
+            <?xml-stylesheet type=\"text/css\" href=\"style.css\" charset=\"iso-8859-1\"?><html></html>";
+        assert_eq!(detect_slice(text, None), vec!["utf-8".to_string()]);
+    }
+
+    #[test]
+    fn test_has_mojibake() {
+        // café, crème and ’ encoded twice
+        assert!(has_mojibake(b"caf\xC3\x83\xC2\xA9"));
+        assert!(has_mojibake(b"cr\xC3\x83\xC2\xA8me"));
+        assert!(has_mojibake(b"it\xC3\xA2\xE2\x82\xAC\xE2\x84\xA2s"));
+        assert!(!has_mojibake("café crème it’s".as_bytes()));
+        assert!(!has_mojibake(b"caf\xE9"));
+    }
+
+    #[test]
+    fn test_mojibake_heuristic() {
+        let text = b"<?xml version=\"1.0\" encoding=\"windows-1252\"?><p>Caf\xC3\x83\xC2\xA9</p>";
+        let options = Options {
+            lenient: true,
+            heuristics: true,
+            ..Options::default()
+        };
+
+        let result = detect_core(text, None, &options);
+        assert_eq!(result.warnings, vec![DetectionWarning::Mojibake]);
+        assert!(result.detections.contains(&Detection {
+            charset: "utf-8".to_string(),
+            source: DetectionSource::Mojibake,
+        }));
+
+        let options = Options {
+            heuristics: true,
+            ..Options::default()
+        };
+        let result = detect_core(text, None, &options);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_search_language() {
+        assert_eq!(
+            search_language(b"<html xml:lang=\"ru-RU\" lang=\"ru\">"),
+            Some("ru".to_string())
+        );
+        assert_eq!(
+            search_language(b"<link hreflang=\"en\"><HTML LANG='JA'>"),
+            Some("ja".to_string())
+        );
+        assert_eq!(search_language(b"<p>lang=ru</p>"), None);
+    }
+
+    #[test]
+    fn test_language_heuristic() {
+        // Привет in windows-1251
+        let text = b"<html lang=\"ru\"><p>\xCF\xF0\xE8\xE2\xE5\xF2</p></html>";
+        let options = Options {
+            heuristics: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["windows-1251".to_string(), "koi8-r".to_string()]
+        );
+        assert!(detect_core(text, None, &Options::default())
+            .detections
+            .is_empty());
+
+        // A declaration always wins
+        let text =
+            b"<html lang=\"ru\"><meta charset=\"iso-8859-5\"><p>\xBF\xE0\xD8\xD2\xD5\xE2</p>";
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["iso-8859-5".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_latin1_as_windows1252() {
+        let text = b"<meta charset=\"ISO-8859-1\"><p>Don\x92t</p>";
+        let options = Options {
+            latin1_as_windows1252: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["windows-1252".to_string()]
+        );
+        assert_eq!(
+            detect_core(text, None, &Options::default()).charsets(),
+            vec!["iso-8859-1".to_string()]
+        );
+
+        // Hints are treated the same
+        let text = b"<p>Don\x92t</p>";
+        assert_eq!(
+            detect_core(text, Some("latin1"), &options).charsets(),
+            vec!["windows-1252".to_string()]
+        );
+
+        // Without C1 bytes ISO-8859-1 is taken at its word
+        let text = b"<meta charset=\"ISO-8859-1\"><p>caf\xE9</p>";
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["iso-8859-1".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_gb18030_heuristic() {
+        // 你好 in GBK
+        let text = b"<html><p>\xC4\xE3\xBA\xC3</p></html>";
+        let options = Options {
+            heuristics: true,
+            ..Options::default()
+        };
+        let result = detect_core(text, None, &options);
+        assert_eq!(result.charsets(), vec!["gb18030".to_string()]);
+        assert_eq!(result.detections[0].source, DetectionSource::ByteRanges);
+        assert!(detect_core(text, None, &Options::default())
+            .detections
+            .is_empty());
+
+        // A lone lead byte isn't GB18030, and is only a half-width katakana in Shift_JIS
+        let text = b"<html><p>\xC4</p></html>";
+        assert!(detect_core(text, None, &options).detections.is_empty());
+    }
+
+    #[test]
+    fn test_multibyte_counts() {
+        let gb18030 = |bytes: &[u8], cut_short| multibyte_counts(bytes, "gb18030", cut_short);
+        assert_eq!(gb18030(b"a\xC4\xE3b\x81\x30\x81\x30", false), Some((2, 0)));
+        assert_eq!(gb18030(b"plain ascii", false), Some((0, 0)));
+        assert_eq!(gb18030(b"\x80\x40", false), None);
+        assert_eq!(gb18030(b"\xC4\xE3\x81\x30", false), None);
+        assert_eq!(gb18030(b"\xC4\xE3\x81\x30", true), Some((1, 0)));
+
+        // こんにちは, カナ and 漢字
+        let shift_jis = b"\x82\xB1\x82\xF1\x82\xC9\x82\xBF\x82\xCD \x83J\x83i \x8A\xBF\x8E\x9A";
+        assert_eq!(
+            multibyte_counts(shift_jis, "shift_jis", false),
+            Some((9, 7))
+        );
+        assert_eq!(multibyte_counts(shift_jis, "euc-jp", false), None);
+        let euc_jp = b"\xA4\xB3\xA4\xF3\xA4\xCB\xA4\xC1\xA4\xCF \xA5\xAB\xA5\xCA \xB4\xC1\xBB\xFA";
+        assert_eq!(multibyte_counts(euc_jp, "euc-jp", false), Some((9, 7)));
+        assert_eq!(multibyte_counts(b"\x8F\xA1", "euc-jp", true), Some((0, 0)));
+        assert_eq!(multibyte_counts(b"\x8F\xA1", "euc-jp", false), None);
+    }
+
+    #[test]
+    fn test_guess_multibyte_charset() {
+        // 日本語のテキスト in Shift_JIS and EUC-JP, and 中文文本 in GBK
+        let shift_jis = b"<p>\x93\xFA\x96{\x8C\xEA\x82\xCC\x83e\x83L\x83X\x83g</p>";
+        assert_eq!(guess_multibyte_charset(shift_jis, false), Some("shift_jis"));
+        let euc_jp = b"<p>\xC6\xFC\xCB\xDC\xB8\xEC\xA4\xCE\xA5\xC6\xA5\xAD\xA5\xB9\xA5\xC8</p>";
+        assert_eq!(guess_multibyte_charset(euc_jp, false), Some("euc-jp"));
+        let gbk = b"<p>\xD6\xD0\xCE\xC4\xCE\xC4\xB1\xBE</p>";
+        assert_eq!(guess_multibyte_charset(gbk, false), Some("gb18030"));
+        assert_eq!(guess_multibyte_charset(b"<p>Hello</p>", false), None);
+        assert_eq!(guess_multibyte_charset(b"<p>\xFF\xFF</p>", false), None);
+    }
+
+    #[test]
+    fn test_root_element_encoding_ignored_when_strict() {
+        let text = b"<?xml version=\"1.0\"?><svg encoding=\"utf-16\"><text>Hi</text></svg>";
+        let options = Options {
+            strict: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["utf-8".to_string()]
+        );
+        assert_eq!(
+            detect_core(text, None, &Options::default()).charsets(),
+            vec!["utf-16be".to_string(), "utf-16le".to_string()]
+        );
+
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><svg encoding=\"utf-8\"/>";
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["iso-8859-1".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_url_declaration_rejected() {
+        let text = b"<?xml version=\"1.0\" encoding=\"http://www.w3.org/2000/svg\"?>\
+            <meta charset=\"windows-1252\">";
+        assert_eq!(detect_slice(text, None), vec!["windows-1252".to_string()]);
+
+        let text = b"<?xml version=\"1.0\" encoding=\"http://www.w3.org/1999/xhtml\"?><p>Hi</p>";
+        assert_eq!(
+            detect_slice(text, Some("iso-8859-1".to_string())),
+            vec!["iso-8859-1".to_string()]
+        );
+        assert_eq!(detect_slice(text, None), vec!["utf-8".to_string()]);
+
+        assert!(is_plausible_charset("ISO_8859-1:1987"));
+        assert!(is_plausible_charset("x-mac-roman"));
+        assert!(!is_plausible_charset("http:"));
+        assert!(!is_plausible_charset("utf-8 utf-16"));
+    }
+
+    #[test]
+    fn test_search_ass_charset() {
+        let text = b"\xEF\xBB\xBF[Script Info]\r\n; Charset: ignored\r\nTitle: Example\r\n\
+            Charset: windows-1251\r\n\r\n[V4+ Styles]\r\n";
+        assert_eq!(search_ass_charset(text), Some("windows-1251".to_string()));
+
+        let text = b"[Script Info]\nTitle: Example\n\n[Events]\nEncoding: cp1252\n";
+        assert_eq!(search_ass_charset(text), None);
+    }
+
+    #[test]
+    fn test_ass_without_charset() {
+        let options = Options {
+            content_kind: ContentKind::Ass,
+            ..Options::default()
+        };
+        let text = "[Script Info]\nTitle: Exemple\n\n[Events]\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Café\n";
+        assert_eq!(
+            detect_core(text.as_bytes(), None, &options).charsets(),
+            vec!["utf-8".to_string()]
+        );
+        assert!(
+            detect_core(b"[Script Info]\nTitle: Caf\xE9\n", None, &options)
+                .detections
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_contradicted_utf8_declaration() {
+        let text = b"<meta charset=\"utf-8\"><p>Caf\xE9</p>";
+        let options = Options {
+            strict: true,
+            ..Options::default()
+        };
+        let result = detect_core(text, Some("windows-1252"), &options);
+        assert_eq!(
+            result.detections,
+            vec![
+                Detection {
+                    charset: "windows-1252".to_string(),
+                    source: DetectionSource::Hint,
+                },
+                Detection {
+                    charset: "utf-8".to_string(),
+                    source: DetectionSource::ContradictedDeclaration,
+                },
+            ]
+        );
+        assert_eq!(
+            result.warnings,
+            vec![DetectionWarning::ContradictedDeclaration]
+        );
+
+        // Only checked in strict mode
+        let result = detect_core(text, None, &Options::default());
+        assert_eq!(result.detections[0].source, DetectionSource::Declaration);
+        assert!(result.warnings.is_empty());
+
+        // Valid UTF-8 upholds the declaration
+        let text = "<meta charset=\"utf-8\"><p>Café</p>".as_bytes();
+        let result = detect_core(text, None, &options);
+        assert_eq!(result.detections[0].source, DetectionSource::Declaration);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_search_rtf_code_page() {
+        let cases: &[(&[u8], Option<&str>)] = &[
+            (b"{\\rtf1\\ansi\\ansicpg1251\\deff0", Some("windows-1251")),
+            (b"{\\rtf1\\ansi\\ansicpg932 ", Some("shift_jis")),
+            (b"{\\rtf1\\ansi\\ansicpg65001}", Some("utf-8")),
+            (b"{\\rtf1\\ansi\\ansicpg437\\deff0", Some("cp437")),
+            (b"{\\rtf1\\ansi\\ansicpg0", None),
+            (b"{\\rtf1\\ansi\\ansicpg\\deff0", None),
+            (b"{\\rtf1\\ansi\\deff0", None),
+        ];
+        for &(text, expected) in cases {
+            assert_eq!(
+                search_rtf_code_page(text).as_deref(),
+                expected,
+                "{:?}",
+                String::from_utf8_lossy(text)
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_css_charset() {
+        let cases: &[(&[u8], Option<&str>)] = &[
+            (
+                b"@charset \"ISO-8859-1\";\nbody { color: red }",
+                Some("iso-8859-1"),
+            ),
+            (b"\xEF\xBB\xBF@charset \"utf-8\";", Some("utf-8")),
+            (b"@charset \"utf-16le\";", Some("utf-8")),
+            (b"@charset \"\";", None),
+            (b"@charset 'utf-8';", None),
+            (b"@charset  \"utf-8\";", None),
+            (b"@CHARSET \"utf-8\";", None),
+            (b"@charset \"utf-8\"", None),
+            (b" @charset \"utf-8\";", None),
+            (b"/* x */ @charset \"utf-8\";", None),
+        ];
+        for &(text, expected) in cases {
+            assert_eq!(
+                search_css_charset(text).as_deref(),
+                expected,
+                "{:?}",
+                String::from_utf8_lossy(text)
+            );
+        }
+    }
+
+    #[test]
+    fn test_css() {
+        let options = Options {
+            content_kind: ContentKind::Css,
+            ..Options::default()
+        };
+        let text = b"@charset \"windows-1252\";\n.quote::before { content: \"\x93\" }";
+        let result = detect_core(text, None, &options);
+        assert_eq!(result.charsets(), vec!["windows-1252".to_string()]);
+        assert_eq!(result.detections[0].source, DetectionSource::Declaration);
+
+        // Markup declarations mean nothing in CSS
+        let text = b"/* <meta charset=\"iso-8859-1\"> */ body { color: red }";
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["utf-8".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_canonicalizer() {
+        let options = Options {
+            canonicalizer: Some(Canonicalizer::new(|charset| charset.to_uppercase())),
+            ..Options::default()
+        };
+        let text = utf16le("<?xml version=\"1.0\" encoding=\"iso-8859-1\"?><p>Hello</p>");
+        let result = detect_core(&text, Some("Windows-1252"), &options);
+        assert_eq!(
+            result.detections,
+            vec![
+                Detection {
+                    charset: "ISO-8859-1".to_string(),
+                    source: DetectionSource::Declaration,
+                },
+                Detection {
+                    charset: "WINDOWS-1252".to_string(),
+                    source: DetectionSource::Hint,
+                },
+                Detection {
+                    charset: "UTF-16LE".to_string(),
+                    source: DetectionSource::ByteOrderMark,
+                },
+            ]
+        );
+
+        // Names that coincide once renamed are returned once
+        let options = Options {
+            canonicalizer: Some(Canonicalizer::new(|_| "text".to_string())),
+            ..Options::default()
+        };
+        assert_eq!(options.clone(), options);
+        assert_ne!(
+            Some(Canonicalizer::new(|_| "text".to_string())),
+            options.canonicalizer
+        );
+        assert_eq!(
+            detect_with_options(&mut Cursor::new(&text), None, &options).unwrap(),
+            vec!["text".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_prefer_utf32_labels() {
+        let options = Options {
+            prefer_utf32_labels: true,
+            ..Options::default()
+        };
+        let cases: &[(&[u8], &str, &str)] = &[
+            (b"\xFF\xFE\x00\x00<\x00\x00\x00", "ucs-4le", "utf-32le"),
+            (b"\x00\x00\xFE\xFF\x00\x00\x00<", "ucs-4be", "utf-32be"),
+            (b"\xFF\xFE<\x00p\x00", "utf-16le", "utf-16le"),
+        ];
+        for &(text, default, preferred) in cases {
+            assert_eq!(
+                detect(&mut Cursor::new(text), None).unwrap(),
+                vec![default.to_string()]
+            );
+            assert_eq!(
+                detect_with_options(&mut Cursor::new(text), None, &options).unwrap(),
+                vec![preferred.to_string()]
+            );
+        }
+    }
+
+    #[test]
+    fn test_delimited_text() {
+        let options = Options {
+            content_kind: ContentKind::DelimitedText,
+            ..Options::default()
+        };
+        let text = b"name,notes\nCaf\xE9,<meta charset=\"windows-1252\">\n";
+        assert!(detect_core(text, None, &options).detections.is_empty());
+        assert_eq!(
+            detect_core(text, Some("windows-1252"), &options).charsets(),
+            vec!["windows-1252".to_string()]
+        );
+        assert_eq!(
+            detect_core(b"\xEF\xBB\xBFname,notes\n", None, &options).detections,
+            vec![Detection {
+                charset: "utf-8".to_string(),
+                source: DetectionSource::ByteOrderMark,
+            }]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_bom_followed_by_truncated_sequence() {
+        let text = b"\xEF\xBB\xBF\xE4";
+        assert_eq!(
+            detect(&mut Cursor::new(text), None).unwrap(),
+            vec!["utf-8".to_string()]
+        );
+        assert_eq!(
+            detect_with_bom_length(&mut Cursor::new(text), None).unwrap(),
+            (vec!["utf-8".to_string()], 3)
+        );
+        let result = detect_detailed(&mut Cursor::new(text), None).unwrap();
+        assert_eq!(
+            result.detections,
+            vec![Detection {
+                charset: "utf-8".to_string(),
+                source: DetectionSource::ByteOrderMark,
+            }]
+        );
+        assert!(result.warnings.is_empty());
+        assert_eq!(detect_slice(text, None), vec!["utf-8".to_string()]);
+
+        let options = Options {
+            strict: true,
+            lenient: true,
+            heuristics: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_with_options(&mut Cursor::new(text), None, &options).unwrap(),
+            vec!["utf-8".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_peek() {
+        let mut text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel>".to_vec();
+        text.resize(2000, b' ');
+        text.extend_from_slice(b"</channel>");
+
+        let mut text_cursor = Cursor::new(&text);
+        assert_eq!(
+            detect_peek(&mut text_cursor, None).unwrap(),
+            vec!["iso-8859-1".to_string()]
+        );
+        assert_eq!(text_cursor.position(), 0);
+
+        let mut reader = io::BufReader::new(Cursor::new(&text));
+        assert_eq!(
+            detect_peek(&mut reader, None).unwrap(),
+            vec!["iso-8859-1".to_string()]
+        );
+        let mut document = Vec::new();
+        reader.read_to_end(&mut document).unwrap();
+        assert_eq!(document, text);
+
+        assert!(detect_peek(&mut Cursor::new(b""), None).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_into() {
+        let mut text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel>".to_vec();
+        text.resize(2000, b' ');
+        text.extend_from_slice(b"</channel>");
+
+        let mut buf = Vec::with_capacity(text.len());
+        let mut text_cursor = Cursor::new(&text);
+        assert_eq!(
+            detect_into(&mut text_cursor, &mut buf, None).unwrap(),
+            vec!["iso-8859-1".to_string()]
+        );
+        assert_eq!(buf, &text[..PREFIX_LEN]);
+
+        text_cursor.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, text);
+
+        // The buffer can be reused for the next text without reallocating
+        let capacity = buf.capacity();
+        buf.clear();
+        assert_eq!(
+            detect_into(&mut Cursor::new(b"\xFF\xFE<\x00p\x00"), &mut buf, None).unwrap(),
+            vec!["utf-16le".to_string()]
+        );
+        assert_eq!(buf, b"\xFF\xFE<\x00p\x00");
+        assert_eq!(buf.capacity(), capacity);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_with_bom_length() {
+        let cases: &[(&[u8], &[&str], usize)] = &[
+            (b"\xEF\xBB\xBF<p>Hello</p>", &["utf-8"], 3),
+            (b"\xFF\xFE<\x00p\x00", &["utf-16le"], 2),
+            (b"\xFE\xFF\x00<\x00p", &["utf-16be"], 2),
+            (b"\x00\x00\xFE\xFF\x00\x00\x00<", &["ucs-4be"], 4),
+            (b"\xFF\xFE\x00\x00<\x00\x00\x00", &["ucs-4le"], 4),
+            (
+                b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>",
+                &["iso-8859-1"],
+                0,
+            ),
+            (b"<p>Hello</p>", &["utf-8"], 0),
+        ];
+        for &(text, charsets, bom_len) in cases {
+            let charsets: Vec<String> =
+                charsets.iter().map(|charset| charset.to_string()).collect();
+            assert_eq!(
+                detect_with_bom_length(&mut Cursor::new(text), None).unwrap(),
+                (charsets, bom_len)
+            );
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_with_spans() {
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel></channel>";
+        assert_eq!(
+            detect_with_spans(&mut Cursor::new(&text[..]), None).unwrap(),
+            (vec!["iso-8859-1".to_string()], Some(20))
+        );
+
+        let text = b"<p>Hello</p>";
+        assert_eq!(
+            detect_with_spans(&mut Cursor::new(&text[..]), None).unwrap(),
+            (vec!["utf-8".to_string()], None)
+        );
+    }
+
+    #[test]
+    fn test_declaration_offset() {
+        // The offset is of the input, not the decoded text, so a character outside the BMP before
+        // the declaration counts for four bytes of UTF-16
+        let mut text = vec![0xFF, 0xFE];
+        for unit in "<!--\u{1F600}--><meta charset=\"utf-16\">".encode_utf16() {
+            text.extend_from_slice(&unit.to_le_bytes());
+        }
+        let result = detect_core(&text, None, &Options::default());
+        assert_eq!(result.declaration_offset, Some(2 + 2 * 15));
+        assert_eq!(&text[32..46], b"c\0h\0a\0r\0s\0e\0t\0");
+
+        // Only the XML declaration is searched when strict
+        let text = b"<!-- encoding --><?xml version=\"1.0\" encoding=\"koi8-r\"?>";
+        let options = Options {
+            strict: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(text, None, &options).declaration_offset,
+            Some(37)
+        );
+
+        // Leading junk is skipped in lenient mode
+        let text = b"\xEF\xBB\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"utf-8\"?>";
+        let options = Options {
+            lenient: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(text, None, &options).declaration_offset,
+            Some(25)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_with_confidence() {
+        let text = b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00 \x00v\x00e\x00r\x00s\x00i\x00o\x00n\x00";
+        assert_eq!(
+            detect_with_confidence(
+                &mut Cursor::new(&text[..]),
+                Some("windows-1252".to_string())
+            )
+            .unwrap(),
+            vec![
+                ("windows-1252".to_string(), Confidence::Hint),
+                ("utf-16le".to_string(), Confidence::Certain),
+            ]
+        );
+
+        assert_eq!(
+            detect_with_confidence(&mut Cursor::new(b"<p>Hello</p>"), None).unwrap(),
+            vec![("utf-8".to_string(), Confidence::Guessed)]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_bytes() {
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel></channel>";
+        assert_eq!(
+            detect_bytes(text, None).unwrap(),
+            vec!["iso-8859-1".to_string()]
+        );
+
+        let text = b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00 \x00v\x00e\x00r\x00s\x00i\x00o\x00n\x00";
+        assert_eq!(
+            detect_bytes(text, None).unwrap(),
+            vec!["utf-16le".to_string()]
+        );
+        assert_eq!(
+            detect_bytes(text, None).unwrap(),
+            detect(&mut Cursor::new(&text[..]), None).unwrap()
+        );
+
+        let err = detect_bytes(b"<p>", None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_record_agreeing_bom() {
+        let text = b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"UTF-8\"?><p>Hello</p>";
+        let options = Options {
+            record_agreeing_bom: true,
+            ..Options::default()
+        };
+
+        let result = detect_core(text, None, &options);
+        assert_eq!(
+            result.detections,
+            vec![
+                Detection {
+                    charset: "utf-8".to_string(),
+                    source: DetectionSource::Declaration,
+                },
+                Detection {
+                    charset: "utf-8".to_string(),
+                    source: DetectionSource::ByteOrderMark,
+                },
+            ]
+        );
+        assert_eq!(result.charsets(), vec!["utf-8".to_string()]);
+
+        let result = detect_core(text, None, &Options::default());
+        assert_eq!(result.detections.len(), 1);
+    }
+
+    #[test]
+    fn test_detection_result_debug() {
+        let text = utf16le("<?xml version=\"1.0\" encoding=\"ISO-8859-1");
+        let result = detect_core(&text, None, &Options::default());
+        assert_eq!(
+            format!("{:?}", result),
+            "DetectionResult { \
+             detections: [\"iso-8859-1\" from declaration (confidence 80), \
+             \"utf-16le\" from bom (confidence 100)], \
+             warnings: [TruncatedDeclaration], \
+             declaration_offset: Some(42) }"
+        );
+        assert_eq!(
+            format!("{:#?}", result),
+            "DetectionResult {
+    detections: [
+        \"iso-8859-1\" from declaration (confidence 80),
+        \"utf-16le\" from bom (confidence 100),
+    ],
+    warnings: [
+        TruncatedDeclaration,
+    ],
+    declaration_offset: Some(
+        42,
+    ),
+}"
+        );
+    }
+
+    #[test]
+    fn test_utf8_prefix_len() {
+        assert_eq!(utf8_prefix_len(b"caf\xC3\xA9", false), Some(5));
+        assert_eq!(utf8_prefix_len(b"caf\xC3", true), Some(3));
+        assert_eq!(utf8_prefix_len(b"caf\xC3", false), None);
+        assert_eq!(utf8_prefix_len(b"\xE2\x82", true), Some(0));
+        assert_eq!(utf8_prefix_len(b"", false), Some(0));
+        assert_eq!(utf8_prefix_len(b"caf\xE9 ", true), None);
+        assert_eq!(utf8_prefix_len(b"caf\xC3\xC3", true), None);
+    }
+
+    #[test]
+    fn test_multibyte_char_straddling_window() {
+        let mut text = b"<p>".to_vec();
+        text.resize(PREFIX_LEN - 1, b'a');
+        text.extend_from_slice("é</p>".as_bytes());
+        assert_eq!(detect_slice(&text, None), vec!["utf-8".to_string()]);
+
+        // A single byte charset character at the end of the window isn't mistaken for UTF-8
+        let mut text = b"<meta charset=\"windows-1252\">".to_vec();
+        text.resize(PREFIX_LEN - 1, b'a');
+        text.push(0xE9);
+        assert_eq!(detect_slice(&text, None), vec!["windows-1252".to_string()]);
+    }
+
+    #[test]
+    fn test_mask_php_blocks() {
+        let mut text = b"a<?PHP echo 'charset=x'; ?>b<?php charset=y".to_vec();
+        mask_php_blocks(&mut text);
+        let expected = [&b"a"[..], &[b' '; 26], b"b", &[b' '; 15]].concat();
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn test_html_php_block_only_ignored_when_strict() {
+        let text = b"<?php $charset='iso-8859-1'; ?><meta charset=\"utf-8\">";
+        let mut options = Options {
+            content_kind: ContentKind::Html,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["iso-8859-1".to_string()]
+        );
+
+        options.strict = true;
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            vec!["utf-8".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_search_po_header() {
+        let text = b"# Translation template\n\
+            #, fuzzy\n\
+            msgid \"\"\n\
+            msgstr \"\"\n\
+            \"Project-Id-Version: example 1.0\\n\"\n\
+            \"Content-Type: text/plain; \"\n\
+            \"charset=CHARSET\\n\"\n\
+            \n\
+            msgid \"charset=utf-8\"\n\
+            msgstr \"\"\n";
+        assert_eq!(search_po_header(text), None);
+
+        let text = b"msgid \"\"\nmsgstr \"Content-Type: text/plain; charset=KOI8-R\\n\"\n";
+        assert_eq!(search_po_header(text), Some("KOI8-R".to_string()));
+    }
+
+    #[test]
+    fn test_po_ignores_other_entries() {
+        let text =
+            b"msgid \"\"\nmsgstr \"\"\n\nmsgid \"<meta charset=\\\"utf-8\\\">\"\nmsgstr \"\"\n";
+        let options = Options {
+            content_kind: ContentKind::Po,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_core(text, None, &options).detections,
+            vec![Detection {
+                charset: "utf-8".to_string(),
+                source: DetectionSource::Utf8Fallback,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_search_vcard_charsets() {
+        let text = b"BEGIN:VCARD\r\nVERSION:2.1\r\n\
+            N;Charset=\"Windows-1252\";ENCODING=8BIT:Doe;Jane\r\n\
+            NOTE;ENCODING=QUOTED-PRINTABLE;CHARSET=utf-8:=C3=A9\r\n\
+            X-EMPTY;CHARSET=:\r\n\
+            END:VCARD\r\n";
+        assert_eq!(
+            search_vcard_charsets(text),
+            vec!["Windows-1252".to_string(), "utf-8".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    struct CountingReader<R> {
+        inner: R,
+        count: usize,
+    }
+
+    #[cfg(feature = "std")]
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.count += n;
+            Ok(n)
+        }
+    }
+
+    // Detect the charset of `text` read through `Take` with both `detect` and `detect_detailed`,
+    // checking that neither reads past `limit`
+    #[cfg(feature = "std")]
+    fn detect_take(text: &[u8], limit: u64) -> (io::Result<Vec<String>>, io::Result<Vec<String>>) {
+        let mut reader = CountingReader {
+            inner: Cursor::new(text),
+            count: 0,
+        };
+        let detected = detect(&mut (&mut reader).take(limit), None);
+        assert!(reader.count as u64 <= limit);
+
+        reader.inner.set_position(0);
+        reader.count = 0;
+        let detailed =
+            detect_detailed(&mut (&mut reader).take(limit), None).map(|result| result.charsets());
+        assert!(reader.count as u64 <= limit);
+
+        (detected, detailed)
+    }
+
+    // A reader that returns at most one byte per read, like a slow network stream
+    #[cfg(feature = "std")]
+    struct ByteReader<'a>(&'a [u8]);
+
+    #[cfg(feature = "std")]
+    impl<'a> Read for ByteReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((&byte, rest)), Some(first)) => {
+                    *first = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_short_reads() {
+        let text = b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00 \x00e\x00n\x00c\x00o\x00d\x00i\x00n\x00g\x00=\x00\"\x00u\x00t\x00f\x00-\x001\x006\x00\"\x00";
+        assert_eq!(
+            detect(&mut ByteReader(text), None).unwrap(),
+            vec!["utf-16le".to_string()]
+        );
+
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel></channel>";
+        assert_eq!(
+            detect(&mut ByteReader(text), None).unwrap(),
+            vec!["iso-8859-1".to_string()]
+        );
+        assert_eq!(
+            detect_with_peeked_bom(*b"<?xm", &mut ByteReader(&text[4..]), None).unwrap(),
+            vec!["iso-8859-1".to_string()]
+        );
+        assert_eq!(
+            detect_timed(&mut ByteReader(text), None).unwrap().0,
+            vec!["iso-8859-1".to_string()]
+        );
+        assert_eq!(
+            detect_detailed(&mut ByteReader(text), None)
+                .unwrap()
+                .charsets(),
+            vec!["iso-8859-1".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_ascii_bytes_decodes_wide_text() {
+        // U+6F3D has = in its low byte, and U+1F600 is a surrogate pair in UTF-16
+        assert_eq!(
+            ascii_bytes(b"a\x00=\x6F=\x00\x3D\xD8\x00\xDE", Some(&UTF_16_LE)),
+            "a\u{6F3D}=\u{1F600}".as_bytes()
+        );
+        assert_eq!(
+            ascii_bytes(b"\x00a\xD8\x00", Some(&UTF_16_BE)),
+            "a\u{FFFD}".as_bytes()
+        );
+        assert_eq!(
+            ascii_bytes(b"\x00\x00a\x00\x01\x00\x00\xF6", Some(&UCS_4_2143)),
+            "a\u{1F600}".as_bytes()
+        );
+        assert_eq!(
+            ascii_bytes(b"\x00a\x00\x00\xF6\x00\x00\x01", Some(&UCS_4_3412)),
+            "a\u{1F600}".as_bytes()
+        );
+        assert_eq!(
+            ascii_bytes(b"\x00\x00\x00a\xFF\xFF\xFF\xFF", Some(&UCS_4_BE)),
+            "a\u{FFFD}".as_bytes()
+        );
+        assert_eq!(ascii_bytes(b"caf\xE9", None), b"caf\xE9");
+    }
+
+    #[test]
+    fn test_wide_text_with_leading_whitespace() {
+        let encode = |text: &str, width: usize, big_endian: bool| -> Vec<u8> {
+            let mut bytes = Vec::new();
+            for byte in text.bytes() {
+                let mut unit = vec![0; width];
+                unit[if big_endian { width - 1 } else { 0 }] = byte;
+                bytes.extend_from_slice(&unit);
+            }
+            bytes
+        };
+        let text = "\r\n  <!-- x --><?xml version=\"1.0\" encoding=\"utf-16\"?>";
+
+        assert_eq!(
+            detect_slice(&encode(text, 2, false), None),
+            vec!["utf-16le".to_string()]
+        );
+        assert_eq!(
+            detect_slice(&encode(text, 2, true), None),
+            vec!["utf-16be".to_string()]
+        );
+
+        let text = "\n<?xml version=\"1.0\" encoding=\"ucs-4le\"?>";
+        assert_eq!(
+            detect_slice(&encode(text, 4, false), None),
+            vec!["ucs-4le".to_string()]
+        );
+        let text = "\n<?xml version=\"1.0\" encoding=\"ucs-4be\"?>";
+        assert_eq!(
+            detect_slice(&encode(text, 4, true), None),
+            vec!["ucs-4be".to_string()]
+        );
+
+        // 8-bit text isn't mistaken for wide text
+        assert_eq!(detect_descriptor_from_first_tag(b"  <p>"), None);
+        assert_eq!(detect_descriptor_from_first_tag(b"\n\x00x\x00<\x00"), None);
+        assert_eq!(detect_descriptor_from_first_tag(b""), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_declaration_straddling_first_four_bytes() {
+        let text = b"<?encoding=\"ISO-8859-1\"?><note>caf\xE9</note>";
+        assert_eq!(find(text, b"encoding="), Some(2));
+
+        let expected = vec!["iso-8859-1".to_string()];
+        assert_eq!(detect(&mut Cursor::new(&text[..]), None).unwrap(), expected);
+        assert_eq!(detect(&mut ByteReader(text), None).unwrap(), expected);
+        assert_eq!(
+            detect_with_peeked_bom(*b"<?en", &mut ByteReader(&text[4..]), None).unwrap(),
+            expected
+        );
+        assert_eq!(
+            detect_timed(&mut ByteReader(text), None).unwrap().0,
+            expected
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_take() {
+        let mut text = b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00".to_vec();
+        text.resize(2000, 0);
+
+        for &limit in &[2, 3] {
+            let (detected, detailed) = detect_take(&text, limit);
+            assert_eq!(detected.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+            assert_eq!(detailed.unwrap(), Vec::<String>::new());
+        }
+
+        for &limit in &[10, 1000] {
+            let (detected, detailed) = detect_take(&text, limit);
+            assert_eq!(detected.unwrap(), vec!["utf-16le".to_string()]);
+            assert_eq!(detailed.unwrap(), vec!["utf-16le".to_string()]);
+        }
+
+        // The declaration is beyond the first 10 bytes
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel></channel>";
+        let (detected, detailed) = detect_take(text, 10);
+        assert_eq!(detected.unwrap(), vec!["utf-8".to_string()]);
+        assert_eq!(detailed.unwrap(), vec!["utf-8".to_string()]);
+        let (detected, detailed) = detect_take(text, 1000);
+        assert_eq!(detected.unwrap(), vec!["iso-8859-1".to_string()]);
+        assert_eq!(detailed.unwrap(), vec!["iso-8859-1".to_string()]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_max_total_read() {
+        let mut reader = CountingReader {
+            inner: io::repeat(b'a'),
+            count: 0,
+        };
+        let options = Options {
+            max_total_read: Some(100),
+            ..Options::default()
+        };
+        let detected_charsets = detect_with_options(&mut reader, None, &options).unwrap();
+        assert_eq!(detected_charsets, vec!["utf-8".to_string()]);
+        assert_eq!(reader.count, 100);
+
+        reader.count = 0;
+        let result = detect_detailed_with_options(&mut reader, None, &options).unwrap();
+        assert_eq!(result.charsets(), vec!["utf-8".to_string()]);
+        assert_eq!(reader.count, 100);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_scan_limit() {
+        let mut text = b"<!-- ".to_vec();
+        text.resize(700, b'-');
+        text.extend_from_slice(b" --><meta charset=\"windows-1252\">");
+
+        assert_eq!(
+            detect(&mut Cursor::new(&text), None).unwrap(),
+            vec!["utf-8".to_string()]
+        );
+
+        let options = Options {
+            scan_limit: 1024,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_with_options(&mut Cursor::new(&text), None, &options).unwrap(),
+            vec!["windows-1252".to_string()]
+        );
+        assert_eq!(
+            detect_detailed_with_options(&mut Cursor::new(&text), None, &options)
+                .unwrap()
+                .charsets(),
+            vec!["windows-1252".to_string()]
+        );
+        assert_eq!(
+            detect_slice_with_options(&text, None, &options),
+            vec!["windows-1252".to_string()]
+        );
+        assert_eq!(
+            detect_peek_with_options(&mut io::BufReader::new(Cursor::new(&text)), None, &options)
+                .unwrap(),
+            vec!["windows-1252".to_string()]
+        );
+        let mut peeked = [0u8; 4];
+        peeked.copy_from_slice(&text[..4]);
+        assert_eq!(
+            detect_with_peeked_bom_with_options(
+                peeked,
+                &mut Cursor::new(&text[4..]),
+                None,
+                &options
+            )
+            .unwrap(),
+            vec!["windows-1252".to_string()]
+        );
+        assert_eq!(
+            DetectorChain::default()
+                .detect_slice_with_options(&text, None, &options)
+                .charsets(),
+            vec!["windows-1252".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_html_prescan() {
+        let mut text =
+            b"<html><head><title>Caf\xE9</title><meta name=\"description\" content=\"".to_vec();
+        text.resize(900, b'-');
+        text.extend_from_slice(b"\"><meta charset=\"windows-1252\"></head>");
+        let options = Options {
+            content_kind: ContentKind::Html,
+            ..Options::default()
+        };
+        assert_eq!(
+            detect_with_options(&mut Cursor::new(&text), None, &options).unwrap(),
+            vec!["windows-1252".to_string()]
+        );
+        assert_eq!(
+            detect(&mut Cursor::new(&text), None).unwrap(),
+            Vec::<String>::new()
+        );
+
+        let text = b"<html><head><title>Caf\xE9</title></HEAD><meta charset=\"windows-1252\">";
+        assert_eq!(
+            detect_core(text, None, &options).charsets(),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            detect_core(text, None, &Options::default()).charsets(),
+            vec!["windows-1252".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_max_total_read_default() {
+        let mut reader = CountingReader {
+            inner: io::repeat(b'a'),
+            count: 0,
+        };
+        detect(&mut reader, None).unwrap();
+        assert_eq!(reader.count, PREFIX_LEN);
+
+        let mut reader = CountingReader {
+            inner: io::repeat(b'a'),
+            count: 0,
+        };
+        detect_timed(&mut reader, None).unwrap();
+        assert_eq!(reader.count, PREFIX_LEN);
+    }
+
+    #[test]
+    fn test_bare_utf16_declaration_8bit() {
+        let text = b"<?xml version=\"1.0\" encoding=\"UTF-16\"?><channel></channel>";
+        assert_eq!(
+            detect_slice(text, None),
+            vec!["utf-16be".to_string(), "utf-16le".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_bare_utf16_hint() {
+        let text = b"<p>Hello</p>";
+        assert_eq!(
+            detect_slice(text, Some("utf-16".to_string())),
+            vec!["utf-16be".to_string(), "utf-16le".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_guess_utf16_byte_order() {
+        assert_eq!(
+            guess_utf16_byte_order(b"<\x00p\x00>\x00"),
+            Some(ByteOrder::LittleEndian)
+        );
+        assert_eq!(
+            guess_utf16_byte_order(b"\x00<\x00p\x00>"),
+            Some(ByteOrder::BigEndian)
+        );
+        assert_eq!(guess_utf16_byte_order(b"<p>"), None);
     }
 }
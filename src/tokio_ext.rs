@@ -0,0 +1,157 @@
+//! Detection on Tokio `AsyncRead` streams.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use {charsets_for_window, Options};
+
+/// The future returned by [`detect_async`](fn.detect_async.html).
+#[must_use = "futures do nothing unless polled"]
+pub struct DetectAsync<'a, R: 'a> {
+    reader: &'a mut R,
+    hint: Option<String>,
+    options: Options,
+    buf: Vec<u8>,
+    len: usize,
+}
+
+impl<'a, R: AsyncRead + Unpin> Future for DetectAsync<'a, R> {
+    type Output = Result<Vec<String>, io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        // Keep reading as streams such as sockets and pipes return short reads
+        while this.len < this.buf.len() {
+            let mut read_buf = ReadBuf::new(&mut this.buf[this.len..]);
+            match Pin::new(&mut *this.reader).poll_read(cx, &mut read_buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(())) if read_buf.filled().is_empty() => break, // eof
+                Poll::Ready(Ok(())) => this.len += read_buf.filled().len(),
+                Poll::Ready(Err(ref err)) if err.kind() == io::ErrorKind::Interrupted => {} // retry
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            }
+        }
+
+        let bytes = &this.buf[..this.len];
+        Poll::Ready(charsets_for_window(
+            bytes,
+            this.hint.as_deref(),
+            &this.options,
+        ))
+    }
+}
+
+/// Attempt to detect the character set of the supplied asynchronous byte stream.
+///
+/// This is the same as [`detect`](fn.detect.html) for a Tokio `AsyncRead`, such as the body of
+/// an HTTP response, without blocking the task. Only the reads are asynchronous, detection itself
+/// is as per `detect`. The returned future resolves to the candidate charsets.
+///
+/// ### Example
+///
+/// ```
+/// extern crate tokio;
+/// extern crate xhtmlchardet;
+///
+/// use tokio::io::BufReader;
+///
+/// let text: &[u8] = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel></channel>";
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let mut reader = BufReader::new(text);
+/// let detected_charsets = runtime.block_on(xhtmlchardet::detect_async(&mut reader, None));
+/// assert_eq!(detected_charsets.unwrap(), vec!["iso-8859-1".to_string()]);
+/// ```
+pub fn detect_async<'a, R: AsyncRead + Unpin>(
+    reader: &'a mut R,
+    hint: Option<String>,
+) -> DetectAsync<'a, R> {
+    let options = Options::default();
+    let len = options.read_limit().min(options.window_len() as u64) as usize;
+    DetectAsync {
+        reader,
+        hint,
+        options,
+        buf: vec![0; len],
+        len: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::BufReader;
+    use tokio::runtime::{Builder, Runtime};
+
+    fn runtime() -> Runtime {
+        Builder::new_current_thread().build().unwrap()
+    }
+
+    // Returns one byte per read, and is pending before each
+    struct TrickleReader<'a> {
+        bytes: &'a [u8],
+        pending: bool,
+    }
+
+    impl<'a> AsyncRead for TrickleReader<'a> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut ReadBuf,
+        ) -> Poll<io::Result<()>> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            if let Some((&byte, rest)) = self.bytes.split_first() {
+                buf.put_slice(&[byte]);
+                self.bytes = rest;
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn test_detect_async_buf_reader() {
+        let text: &[u8] = b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00";
+        let detected = runtime()
+            .block_on(detect_async(&mut BufReader::new(text), None))
+            .unwrap();
+        assert_eq!(detected, vec!["utf-16le".to_string()]);
+    }
+
+    #[test]
+    fn test_detect_async_short_reads() {
+        let text = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><channel></channel>";
+        let mut reader = TrickleReader {
+            bytes: text,
+            pending: false,
+        };
+        let detected = runtime().block_on(detect_async(&mut reader, None)).unwrap();
+        assert_eq!(detected, vec!["iso-8859-1".to_string()]);
+    }
+
+    #[test]
+    fn test_detect_async_short_stream() {
+        let runtime = runtime();
+        let detected = runtime
+            .block_on(detect_async(&mut BufReader::new(&b""[..]), None))
+            .unwrap();
+        assert!(detected.is_empty());
+
+        let error = runtime
+            .block_on(detect_async(&mut BufReader::new(&b"<p"[..]), None))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        let detected = runtime
+            .block_on(detect_async(&mut BufReader::new(&b"<p>H"[..]), None))
+            .unwrap();
+        assert!(detected.is_empty());
+    }
+}
@@ -0,0 +1,460 @@
+//! Detection on text that has been encoded for transfer, such as base64 email parts.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io;
+
+use {detect_slice, is_plausible_charset, normalise, parse_hint, PREFIX_LEN};
+
+/// Attempt to detect the character set of base64 encoded text.
+///
+/// Whitespace, including line breaks, in `b64` is ignored. Only enough of `b64` is decoded to
+/// fill the window examined by [`detect_slice`](fn.detect_slice.html), which is then used to
+/// detect the charset. The optional `hint` is as per [`detect`](fn.detect.html).
+///
+/// An error of kind `InvalidData` is returned if `b64` contains characters that aren't valid
+/// base64.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// // <?xml version="1.0" encoding="ISO-8859-1"?>
+/// let b64 = b"PD94bWwgdmVyc2lvbj0iMS4wIiBlbmNv\r\nZGluZz0iSVNPLTg4NTktMSI/Pg==";
+/// let detected_charsets = xhtmlchardet::detect_base64(b64, None);
+/// assert_eq!(detected_charsets.unwrap_or(vec![]), vec!["iso-8859-1".to_string()]);
+/// ```
+#[cfg(feature = "std")]
+pub fn detect_base64(b64: &[u8], hint: Option<String>) -> Result<Vec<String>, io::Error> {
+    let bytes = decode_base64(b64, PREFIX_LEN).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid character in base64 input",
+        )
+    })?;
+    Ok(detect_slice(&bytes, hint))
+}
+
+/// Attempt to detect the character set of the body of a mail message, such as one read from a
+/// Maildir or mbox.
+///
+/// The top-level headers of `raw` are parsed, with the `charset` parameter of the `Content-Type`
+/// header used as the hint. A body with a `Content-Transfer-Encoding` of `quoted-printable` or
+/// `base64` is decoded before detection. Only enough of the body to fill the window examined by
+/// [`detect_slice`](fn.detect_slice.html) is decoded. The parts of multipart messages are not
+/// examined individually.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// let message = b"From: sender@example.com\r\n\
+///     Content-Type: text/plain; charset=\"ISO-8859-1\"\r\n\
+///     Content-Transfer-Encoding: quoted-printable\r\n\
+///     \r\n\
+///     Caf=E9\r\n";
+/// let detected_charsets = xhtmlchardet::detect_mail_message(message);
+/// assert_eq!(detected_charsets, vec!["iso-8859-1".to_string()]);
+/// ```
+pub fn detect_mail_message(raw: &[u8]) -> Vec<String> {
+    let (headers, body) = split_headers(raw);
+
+    let mut hint = None;
+    let mut transfer_encoding = None;
+    for (name, value) in headers {
+        if name.eq_ignore_ascii_case("content-type") {
+            hint = charset_from_content_type(&value);
+        } else if name.eq_ignore_ascii_case("content-transfer-encoding") {
+            transfer_encoding = Some(value.trim().to_ascii_lowercase());
+        }
+    }
+
+    let decoded = match transfer_encoding.as_deref() {
+        Some("quoted-printable") => decode_quoted_printable(body, PREFIX_LEN),
+        // Mail in the wild is often slightly malformed, so make the best of what decodes
+        Some("base64") => decode_base64(body, PREFIX_LEN).unwrap_or_default(),
+        _ => body.to_vec(),
+    };
+    detect_slice(&decoded, hint)
+}
+
+/// Attempt to detect the character set of an `application/x-www-form-urlencoded` body.
+///
+/// The charset is taken from a `_charset_` field in `body`, as sent by browsers when the form
+/// includes a hidden field of that name. Otherwise the optional `hint`, such as the form's
+/// `accept-charset`, is used. Failing both, `utf-8` is returned if the decoded values are valid
+/// UTF-8. The body is never searched for a markup declaration, so a field named `charset` isn't
+/// mistaken for one.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// let body = b"name=Caf%E9&_charset_=windows-1252";
+/// let detected_charsets = xhtmlchardet::detect_form_urlencoded(body, None);
+/// assert_eq!(detected_charsets, vec!["windows-1252".to_string()]);
+/// ```
+pub fn detect_form_urlencoded(body: &[u8], hint: Option<String>) -> Vec<String> {
+    let mut charsets = Vec::new();
+    let mut decoded = Vec::new();
+
+    for field in body.split(|&byte| byte == b'&') {
+        let (name, value) = match field.iter().position(|&byte| byte == b'=') {
+            Some(equals) => (&field[..equals], &field[equals + 1..]),
+            None => (field, &field[field.len()..]),
+        };
+        let value = decode_percent(value);
+        if name == b"_charset_" && charsets.is_empty() {
+            let charset = String::from_utf8_lossy(&value);
+            if is_plausible_charset(&charset) {
+                charsets.push(normalise(charset));
+            }
+        }
+        decoded.extend_from_slice(&decode_percent(name));
+        decoded.extend_from_slice(&value);
+    }
+
+    for charset in hint.iter().flat_map(|hint| parse_hint(hint)) {
+        if !charsets.contains(&charset) {
+            charsets.push(charset);
+        }
+    }
+
+    if charsets.is_empty() && core::str::from_utf8(&decoded).is_ok() {
+        charsets.push("utf-8".to_string());
+    }
+    charsets
+}
+
+// Decode a percent-encoded form name or value, where + is a space. Malformed escapes are kept as
+// is.
+fn decode_percent(input: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(input.len());
+    let mut index = 0;
+
+    while index < input.len() {
+        match input[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                if let Some(value) = input.get(index + 1..index + 3).and_then(decode_hex) {
+                    decoded.push(value);
+                    index += 3;
+                    continue;
+                }
+                decoded.push(b'%');
+            }
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+
+    decoded
+}
+
+// Decode a pair of hex digits. `from_str_radix` alone would accept a leading sign, such as `+A`.
+fn decode_hex(digits: &[u8]) -> Option<u8> {
+    if !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    core::str::from_utf8(digits)
+        .ok()
+        .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+}
+
+// Split a message into its unfolded headers and its body
+fn split_headers(raw: &[u8]) -> (Vec<(String, String)>, &[u8]) {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut rest = raw;
+
+    while !rest.is_empty() {
+        let (line, next) = match rest.iter().position(|&byte| byte == b'\n') {
+            Some(end) => (&rest[..end], &rest[end + 1..]),
+            None => (rest, &rest[rest.len()..]),
+        };
+        let line = match line.last() {
+            Some(&b'\r') => &line[..line.len() - 1],
+            _ => line,
+        };
+        rest = next;
+
+        if line.is_empty() {
+            // The blank line separating the headers from the body
+            break;
+        }
+
+        let line = String::from_utf8_lossy(line);
+        if line.starts_with([' ', '\t']) {
+            // A folded continuation of the previous header
+            if let Some(&mut (_, ref mut value)) = headers.last_mut() {
+                value.push_str(&line);
+            }
+        } else if let Some(colon) = line.find(':') {
+            headers.push((
+                line[..colon].trim().to_string(),
+                line[colon + 1..].to_string(),
+            ));
+        }
+    }
+
+    (headers, rest)
+}
+
+/// Extract the charset parameter from the value of a `Content-Type` header.
+///
+/// This is what should be passed as the `hint` to [`detect`](fn.detect.html) when the text came
+/// with a `Content-Type` header, as the whole header would otherwise be taken to be the name of
+/// a charset. The parameter name is matched regardless of case, and whitespace and quotes around
+/// the value are removed. The RFC 2231 extended form, `charset*=UTF-8''...`, is also recognised,
+/// in which case the charset is the part before the `''`. It takes precedence over a plain
+/// `charset=`. `None` is returned if there is no charset parameter.
+///
+/// ### Example
+///
+/// ```
+/// extern crate xhtmlchardet;
+///
+/// let hint = xhtmlchardet::charset_from_content_type("text/html; Charset=\"ISO-8859-1\"");
+/// assert_eq!(hint, Some("ISO-8859-1".to_string()));
+/// assert_eq!(xhtmlchardet::charset_from_content_type("text/html"), None);
+/// ```
+pub fn charset_from_content_type(value: &str) -> Option<String> {
+    let mut plain = None;
+    for parameter in value.split(';').skip(1) {
+        let equals = match parameter.find('=') {
+            Some(equals) => equals,
+            None => continue,
+        };
+        let name = parameter[..equals].trim();
+        let value = parameter[equals + 1..].trim().trim_matches('"').trim();
+
+        if name.eq_ignore_ascii_case("charset*") {
+            let charset = value.split('\'').next().unwrap_or("");
+            if !charset.is_empty() {
+                return Some(charset.to_string());
+            }
+        } else if name.eq_ignore_ascii_case("charset") && plain.is_none() {
+            let value = value.trim_matches('\'').trim();
+            if !value.is_empty() {
+                plain = Some(value.to_string());
+            }
+        }
+    }
+
+    plain
+}
+
+// Decode at most `limit` bytes of quoted-printable encoded `input`. Malformed escapes are kept
+// as is.
+fn decode_quoted_printable(input: &[u8], limit: usize) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(limit.min(input.len()));
+    let mut index = 0;
+
+    while index < input.len() && decoded.len() < limit {
+        if input[index] == b'=' {
+            let rest = &input[index + 1..];
+            // Soft line break
+            if rest.starts_with(b"\r\n") {
+                index += 3;
+                continue;
+            } else if rest.starts_with(b"\n") {
+                index += 2;
+                continue;
+            }
+
+            if let Some(value) = rest.get(..2).and_then(decode_hex) {
+                decoded.push(value);
+                index += 3;
+                continue;
+            }
+        }
+
+        decoded.push(input[index]);
+        index += 1;
+    }
+
+    decoded
+}
+
+/// Decode at most `limit` bytes of base64 encoded `input`, ignoring whitespace. Returns `None` if
+/// `input` contains characters that aren't valid base64.
+pub(crate) fn decode_base64(input: &[u8], limit: usize) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(limit.min(input.len() / 4 * 3));
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+
+    for &byte in input {
+        if decoded.len() >= limit {
+            break;
+        }
+
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ if byte.is_ascii_whitespace() => continue,
+            _ => return None,
+        };
+
+        bits = (bits << 6) | u32::from(value);
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            decoded.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(decode_base64(b"aGVsbG8=", 100).unwrap(), b"hello");
+        assert_eq!(decode_base64(b"aGVs\r\nbG8h", 100).unwrap(), b"hello!");
+        assert_eq!(decode_base64(b"aGVsbG8=", 2).unwrap(), b"he");
+        assert!(decode_base64(b"aGV*bG8=", 100).is_none());
+    }
+
+    #[test]
+    fn test_decode_quoted_printable() {
+        assert_eq!(decode_quoted_printable(b"Caf=E9", 100), b"Caf\xE9");
+        assert_eq!(
+            decode_quoted_printable(b"soft=\r\nbreak=\nhere", 100),
+            b"softbreakhere"
+        );
+        assert_eq!(decode_quoted_printable(b"1=2 =zz", 100), b"1=2 =zz");
+        assert_eq!(decode_quoted_printable(b"=+A", 100), b"=+A");
+        assert_eq!(decode_quoted_printable(b"=E9=E9=E9", 2), b"\xE9\xE9");
+    }
+
+    #[test]
+    fn test_content_type_charset() {
+        assert_eq!(
+            charset_from_content_type(" text/plain; format=flowed; CharSet=\"utf-8\""),
+            Some("utf-8".to_string())
+        );
+        assert_eq!(charset_from_content_type("text/plain"), None);
+        assert_eq!(charset_from_content_type("text/plain; charset="), None);
+        assert_eq!(charset_from_content_type("text/plain; charset=\"\""), None);
+        assert_eq!(
+            charset_from_content_type("text/html;charset = ' windows-1252 ' ;q=1"),
+            Some("windows-1252".to_string())
+        );
+        assert_eq!(
+            charset_from_content_type("text/html; charset=UTF-8; boundary=x"),
+            Some("UTF-8".to_string())
+        );
+    }
+
+    #[test]
+    fn test_content_type_charset_extended() {
+        assert_eq!(
+            charset_from_content_type("text/plain; charset*=UTF-8''%41"),
+            Some("UTF-8".to_string())
+        );
+        assert_eq!(
+            charset_from_content_type("text/plain; charset=us-ascii; Charset*=iso-8859-1'en'"),
+            Some("iso-8859-1".to_string())
+        );
+        assert_eq!(
+            charset_from_content_type("text/plain; charset*=''%41"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_detect_mail_message_extended_charset() {
+        let message = b"Content-Type: text/plain; charset*=ISO-8859-1'fr'%41\r\n\r\ncaf\xE9\r\n";
+        assert_eq!(detect_mail_message(message), vec!["iso-8859-1".to_string()]);
+    }
+
+    #[test]
+    fn test_detect_mail_message_quoted_printable() {
+        let message = b"From: sender@example.com\r\n\
+            Subject: Caf=?iso-8859-1?q?=E9?=\r\n\
+            Content-Type: text/html;\r\n\tcharset=\"ISO-8859-1\"\r\n\
+            Content-Transfer-Encoding: quoted-printable\r\n\
+            \r\n\
+            <p>Caf=E9 cr=E8me br=FBl=E9e, s'il vous pla=EEt. Un tr=E8s long paragraphe qui=\r\n \
+            continue sur la ligne suivante.</p>\r\n";
+        assert_eq!(detect_mail_message(message), vec!["iso-8859-1".to_string()]);
+    }
+
+    #[test]
+    fn test_detect_mail_message_base64() {
+        // <?xml version="1.0" encoding="ISO-8859-1"?>
+        let message = b"Content-Type: application/xml\n\
+            Content-Transfer-Encoding: BASE64\n\
+            \n\
+            PD94bWwgdmVyc2lvbj0iMS4wIiBlbmNv\n\
+            ZGluZz0iSVNPLTg4NTktMSI/Pg==\n";
+        assert_eq!(detect_mail_message(message), vec!["iso-8859-1".to_string()]);
+    }
+
+    #[test]
+    fn test_detect_mail_message_without_headers() {
+        assert_eq!(detect_mail_message(b"\nHello"), vec!["utf-8".to_string()]);
+    }
+
+    #[test]
+    fn test_decode_percent() {
+        assert_eq!(
+            decode_percent(b"Caf%E9+cr%c3%a8me"),
+            b"Caf\xE9 cr\xC3\xA8me"
+        );
+        assert_eq!(decode_percent(b"100%+%zz%4"), b"100% %zz%4");
+        assert_eq!(decode_percent(b"%+A%-1"), b"% A%-1");
+    }
+
+    #[test]
+    fn test_detect_form_urlencoded_charset_field() {
+        let body = b"_charset_=windows-1252&charset=utf-8&comment=%3Cmeta+charset%3D%22utf-8%22%3E";
+        assert_eq!(
+            detect_form_urlencoded(body, Some("iso-8859-1".to_string())),
+            vec!["windows-1252".to_string(), "iso-8859-1".to_string()]
+        );
+
+        let body = b"_charset_=http%3A%2F%2Fexample.com&_charset_=&name=Caf%C3%A9";
+        assert_eq!(
+            detect_form_urlencoded(body, None),
+            vec!["utf-8".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_detect_form_urlencoded_without_charset_field() {
+        let body = b"charset=shift_jis&name=Caf%E9";
+        assert_eq!(
+            detect_form_urlencoded(body, Some("ISO-8859-1".to_string())),
+            vec!["iso-8859-1".to_string()]
+        );
+        assert_eq!(detect_form_urlencoded(body, None), Vec::<String>::new());
+        assert_eq!(
+            detect_form_urlencoded(b"name=Caf%C3%A9", None),
+            vec!["utf-8".to_string()]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_base64_utf16_bom() {
+        // UTF-16LE BOM followed by <?xml version="1.0"?>
+        let b64 = b"//48AD8AeABtAGwAIAB2AGUAcgBzAGkAbwBuAD0AIgAxAC4AMAAiAD8APgA=";
+        assert_eq!(
+            detect_base64(b64, None).unwrap(),
+            vec!["utf-16le".to_string()]
+        );
+    }
+}
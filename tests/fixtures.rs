@@ -1,3 +1,7 @@
+#![allow(unknown_lints, non_local_definitions)]
+
+#[cfg(feature = "tokio")]
+extern crate tokio;
 extern crate toml;
 extern crate xhtmlchardet;
 #[macro_use]
@@ -5,7 +9,7 @@ extern crate serde_derive;
 
 use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{Cursor, Read, Write};
 
 #[derive(Deserialize)]
 struct Test {
@@ -13,6 +17,11 @@ struct Test {
     charset: Vec<String>,
     variant: String,
     // content_type_header: String,
+    lenient: Option<bool>,
+    strict: Option<bool>,
+    heuristics: Option<bool>,
+    latin1_as_windows1252: Option<bool>,
+    content_kind: Option<String>,
 }
 
 fn read_config() -> Vec<Test> {
@@ -21,12 +30,27 @@ fn read_config() -> Vec<Test> {
     file.read_to_string(&mut toml)
         .expect("Error reading config file");
     let mut config: HashMap<String, Vec<Test>> =
-        toml::decode_str(&toml).expect("Error parsing config file");
+        toml::from_str(&toml).expect("Error parsing config file");
     config
         .remove("fixtures")
         .expect("no fixtures in config file")
 }
 
+fn content_kind(name: Option<&String>) -> xhtmlchardet::ContentKind {
+    match name.map(|name| name.as_str()) {
+        None => xhtmlchardet::ContentKind::Markup,
+        Some("html") => xhtmlchardet::ContentKind::Html,
+        Some("office-xml") => xhtmlchardet::ContentKind::OfficeXml,
+        Some("po") => xhtmlchardet::ContentKind::Po,
+        Some("vcard") => xhtmlchardet::ContentKind::VCard,
+        Some("ass") => xhtmlchardet::ContentKind::Ass,
+        Some("delimited-text") => xhtmlchardet::ContentKind::DelimitedText,
+        Some("rtf") => xhtmlchardet::ContentKind::Rtf,
+        Some("css") => xhtmlchardet::ContentKind::Css,
+        Some(name) => panic!("unknown content_kind {}", name),
+    }
+}
+
 #[test]
 fn test_fixtures() {
     let tests = read_config();
@@ -39,8 +63,16 @@ fn test_fixtures() {
         let path = format!("tests/{}-{}.txt", &test.charset[0], &test.variant);
         expected.insert(path.clone(), test.charset);
 
-        let mut file = File::open(&path).expect(&format!("Unable to open {}", path));
-        let actual_charset = xhtmlchardet::detect(&mut file, None);
+        let mut file = File::open(&path).unwrap_or_else(|_| panic!("Unable to open {}", path));
+        let options = xhtmlchardet::Options {
+            lenient: test.lenient.unwrap_or(false),
+            strict: test.strict.unwrap_or(false),
+            heuristics: test.heuristics.unwrap_or(false),
+            latin1_as_windows1252: test.latin1_as_windows1252.unwrap_or(false),
+            content_kind: content_kind(test.content_kind.as_ref()),
+            ..Default::default()
+        };
+        let actual_charset = xhtmlchardet::detect_with_options(&mut file, None, &options);
         actual.insert(path, actual_charset.unwrap());
     }
 
@@ -49,13 +81,99 @@ fn test_fixtures() {
     let mut f = std::io::stderr();
     for (test, result) in expected.iter() {
         if *result != actual[test] {
-            f.write(format!("FAIL {}: {:?} != {:?}\n", test, actual[test], result).as_bytes())
+            f.write_all(format!("FAIL {}: {:?} != {:?}\n", test, actual[test], result).as_bytes())
                 .unwrap();
         } else {
             passed += 1;
-            // f.write(format!("PASS {}: {:?}\n", test, actual[test]).as_bytes());
+            // f.write_all(format!("PASS {}: {:?}\n", test, actual[test]).as_bytes());
         }
     }
 
     assert_eq!(passed, expected.len());
 }
+
+#[test]
+fn test_entry_point_parity() {
+    let hints = [
+        None,
+        Some("utf-8".to_string()),
+        Some("windows-1251, iso-8859-1;q=0.5".to_string()),
+    ];
+
+    for test in read_config() {
+        let path = format!("tests/{}-{}.txt", &test.charset[0], &test.variant);
+        let mut bytes = Vec::new();
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .unwrap_or_else(|_| panic!("Unable to read {}", path));
+
+        for hint in &hints {
+            let expected = xhtmlchardet::detect_slice(&bytes, hint.clone());
+            let detected = xhtmlchardet::detect(&mut Cursor::new(&bytes), hint.clone()).unwrap();
+            assert_eq!(detected, expected, "detect {} with hint {:?}", path, hint);
+            let detected = xhtmlchardet::detect_bytes(&bytes, hint.clone()).unwrap();
+            assert_eq!(
+                detected, expected,
+                "detect_bytes {} with hint {:?}",
+                path, hint
+            );
+            let detailed =
+                xhtmlchardet::detect_detailed(&mut Cursor::new(&bytes), hint.clone()).unwrap();
+            assert_eq!(
+                detailed.charsets(),
+                expected,
+                "detect_detailed {} with hint {:?}",
+                path,
+                hint
+            );
+            #[cfg(feature = "tokio")]
+            {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .build()
+                    .unwrap();
+                let mut reader = tokio::io::BufReader::new(&bytes[..]);
+                let detected = runtime
+                    .block_on(xhtmlchardet::detect_async(&mut reader, hint.clone()))
+                    .unwrap();
+                assert_eq!(
+                    detected, expected,
+                    "detect_async {} with hint {:?}",
+                    path, hint
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_short_input_parity() {
+    let texts: &[&[u8]] = &[
+        b"",
+        b"<p",
+        b"<p>H",
+        b"\xFF\xFE<\x00",
+        b"<p>Hi",
+        b"\xEF\xBB\xBF<",
+    ];
+
+    for &text in texts {
+        let detected = xhtmlchardet::detect(&mut Cursor::new(text), None).map_err(|err| err.kind());
+        assert_eq!(
+            xhtmlchardet::detect_bytes(text, None).map_err(|err| err.kind()),
+            detected,
+            "detect_bytes {:?}",
+            text
+        );
+        #[cfg(feature = "tokio")]
+        {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            let mut reader = tokio::io::BufReader::new(text);
+            let detected_async = runtime
+                .block_on(xhtmlchardet::detect_async(&mut reader, None))
+                .map_err(|err| err.kind());
+            assert_eq!(detected_async, detected, "detect_async {:?}", text);
+        }
+    }
+}
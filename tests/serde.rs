@@ -0,0 +1,52 @@
+#![cfg(feature = "serde")]
+
+extern crate serde_json;
+extern crate xhtmlchardet;
+
+use std::io::Cursor;
+
+use xhtmlchardet::{BomKind, Confidence, DetectionResult, DetectionSource};
+
+#[test]
+fn test_detection_result_round_trip() {
+    let text = b"\xFF\xFE<\x00?\x00x\x00m\x00l\x00 \x00e\x00n\x00c\x00o\x00d\x00i\x00n\x00g\x00=\x00\"\x00u\x00t\x00f\x00-\x008\x00\"\x00";
+    let options = xhtmlchardet::Options {
+        strict: true,
+        ..xhtmlchardet::Options::default()
+    };
+    let result =
+        xhtmlchardet::detect_detailed_with_options(&mut Cursor::new(&text[..]), None, &options)
+            .unwrap();
+    assert!(!result.warnings.is_empty());
+
+    let json = serde_json::to_string(&result).unwrap();
+    let decoded: DetectionResult = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, result);
+}
+
+#[test]
+fn test_enum_names() {
+    assert_eq!(
+        serde_json::to_string(&DetectionSource::ByteOrderMark).unwrap(),
+        "\"bom\""
+    );
+    assert_eq!(
+        serde_json::to_string(&DetectionSource::Utf8Fallback).unwrap(),
+        "\"utf8-fallback\""
+    );
+    assert_eq!(
+        serde_json::to_string(&Confidence::Declared).unwrap(),
+        "\"declared\""
+    );
+    assert_eq!(
+        serde_json::from_str::<Confidence>("\"certain\"").unwrap(),
+        Confidence::Certain
+    );
+
+    let bom = xhtmlchardet::detect_bom(b"\xEF\xBB\xBF<").unwrap();
+    let json = serde_json::to_string(&bom).unwrap();
+    assert_eq!(
+        serde_json::from_str::<BomKind>(&json).unwrap(),
+        BomKind::Utf8
+    );
+}